@@ -0,0 +1,184 @@
+#[cfg(not(target_os = "windows"))]
+const MVN: &str = "mvn";
+#[cfg(target_os = "windows")]
+const MVN: &str = "mvn.cmd";
+#[cfg(not(target_os = "windows"))]
+const GRADLE: &str = "gradle";
+#[cfg(target_os = "windows")]
+const GRADLE: &str = "gradle.bat";
+#[cfg(not(target_os = "windows"))]
+const FLUTTER: &str = "flutter";
+#[cfg(target_os = "windows")]
+const FLUTTER: &str = "flutter.bat";
+
+/// A built-in rule of the projects marked by a file.
+#[derive(Debug)]
+pub(crate) struct Builtin {
+    /// The marker file, a file name or a glob of a single `*`, e.g: `*.csproj`.
+    pub marker: &'static str,
+    /// The ecosystem selected by `--only` and `--skip`, e.g: `cargo`.
+    pub ecosystem: &'static str,
+    /// The clean command and its arguments, empty if there is none.
+    pub clean: &'static [&'static str],
+    /// Build outputs relative to the work dir, which the clean command cleans.
+    pub outputs: &'static [&'static str],
+    /// Build directories removed only when the marker sits next to them.
+    pub build_dirs: &'static [&'static str],
+}
+
+impl Builtin {
+    const fn new(marker: &'static str, ecosystem: &'static str) -> Builtin {
+        Builtin {
+            marker,
+            ecosystem,
+            clean: &[],
+            outputs: &[],
+            build_dirs: &[],
+        }
+    }
+
+    const fn clean(self, clean: &'static [&'static str]) -> Builtin {
+        Builtin { clean, ..self }
+    }
+
+    const fn outputs(self, outputs: &'static [&'static str]) -> Builtin {
+        Builtin { outputs, ..self }
+    }
+
+    const fn build_dirs(self, build_dirs: &'static [&'static str]) -> Builtin {
+        Builtin { build_dirs, ..self }
+    }
+
+    /// Whether the built-in cleans anything by itself, rather than naming the
+    /// ecosystem of the custom rules only, e.g: `package.json`.
+    pub fn cleans(&self) -> bool {
+        !self.clean.is_empty() || !self.build_dirs.is_empty()
+    }
+}
+
+/// The only table of the built-in rules, the first marker matched wins.
+pub(crate) const BUILTINS: [Builtin; 16] = [
+    Builtin::new("Cargo.toml", "cargo")
+        .clean(&["cargo", "clean"])
+        .outputs(&["target"]),
+    Builtin::new("go.mod", "go").clean(&["go", "clean"]),
+    Builtin::new("pom.xml", "maven")
+        .clean(&[MVN, "clean"])
+        .outputs(&["target"]),
+    Builtin::new("build.gradle", "gradle")
+        .clean(&[GRADLE, "clean"])
+        .outputs(&["build"]),
+    Builtin::new("CMakeLists.txt", "cmake").build_dirs(&["build", "cmake-build-*"]),
+    Builtin::new("meson.build", "meson").build_dirs(&["build", "builddir"]),
+    Builtin::new("WORKSPACE", "bazel").clean(&["bazel", "clean"]),
+    Builtin::new("WORKSPACE.bazel", "bazel").clean(&["bazel", "clean"]),
+    Builtin::new("MODULE.bazel", "bazel").clean(&["bazel", "clean"]),
+    Builtin::new("Makefile", "make").clean(&["make", "clean"]),
+    Builtin::new("makefile", "make").clean(&["make", "clean"]),
+    Builtin::new("GNUmakefile", "make").clean(&["make", "clean"]),
+    Builtin::new("*.sln", "dotnet")
+        .clean(&["dotnet", "clean"])
+        .outputs(&["bin", "obj"]),
+    Builtin::new("*.csproj", "dotnet")
+        .clean(&["dotnet", "clean"])
+        .outputs(&["bin", "obj"])
+        .build_dirs(&["bin", "obj"]),
+    Builtin::new("Package.swift", "swift")
+        .clean(&["swift", "package", "clean"])
+        .outputs(&[".build"]),
+    Builtin::new("pubspec.yaml", "flutter")
+        .clean(&[FLUTTER, "clean"])
+        .outputs(&["build", ".dart_tool"]),
+];
+
+/// Node projects are cleaned by the custom rules only, e.g: `node_modules/`,
+/// which are named by the ecosystem as well.
+const NODE: Builtin = Builtin::new("package.json", "node").outputs(&["node_modules"]);
+
+/// All of the built-ins, including those which clean nothing by themselves.
+pub(crate) fn all() -> impl Iterator<Item = &'static Builtin> {
+    BUILTINS.iter().chain([&NODE])
+}
+
+/// The built-in marked by the `file`.
+pub(crate) fn find(file: &str) -> Option<&'static Builtin> {
+    all().find(|builtin| matches(builtin.marker, file))
+}
+
+/// The ecosystem of the rule `name`, which is either the marker, or the build
+/// output of a single ecosystem, e.g: `node_modules`.
+pub(crate) fn ecosystem(name: &str) -> Option<&'static str> {
+    if let Some(builtin) = find(name) {
+        return Some(builtin.ecosystem);
+    }
+    let mut ecosystems = all()
+        .filter(|builtin| builtin.outputs.contains(&name))
+        .map(|builtin| builtin.ecosystem);
+    let ecosystem = ecosystems.next()?;
+    return ecosystems
+        .all(|each| each == ecosystem)
+        .then_some(ecosystem);
+}
+
+/// The tool of the `command`, the wrappers and the windows scripts run the same
+/// tools, e.g: `./gradlew`, `mvn.cmd`.
+pub(crate) fn tool(command: &str) -> &str {
+    let tool = command.trim_start_matches("./");
+    let tool = [".bat", ".cmd"]
+        .iter()
+        .find_map(|ext| tool.strip_suffix(ext))
+        .unwrap_or(tool);
+    return match tool {
+        "gradlew" => "gradle",
+        "mvnw" => "mvn",
+        tool => tool,
+    };
+}
+
+/// Whether the `name` matches the `pattern`, which is either a name or a glob
+/// of a single `*`, so it's never compiled as a glob.
+pub(crate) fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => {
+            name.len() > prefix.len() + suffix.len()
+                && name.starts_with(prefix)
+                && name.ends_with(suffix)
+        }
+        None => pattern == name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ecosystem, find, matches, tool};
+
+    #[test]
+    fn match_names_and_single_star_globs() {
+        assert!(matches("Cargo.toml", "Cargo.toml"));
+        assert!(!matches("Cargo.toml", "cargo.toml"));
+        assert!(matches("*.csproj", "App.csproj"));
+        assert!(!matches("*.csproj", ".csproj"));
+        assert!(matches("cmake-build-*", "cmake-build-debug"));
+        assert!(!matches("cmake-build-*", "build"));
+    }
+
+    #[test]
+    fn ecosystems_by_marker_or_single_output() {
+        assert_eq!(ecosystem("Cargo.toml"), Some("cargo"));
+        assert_eq!(ecosystem("App.sln"), Some("dotnet"));
+        assert_eq!(ecosystem("package.json"), Some("node"));
+        assert_eq!(ecosystem("node_modules"), Some("node"));
+        assert_eq!(ecosystem(".dart_tool"), Some("flutter"));
+        assert_eq!(ecosystem("target"), None, "cargo or maven");
+        assert_eq!(ecosystem("dist"), None);
+        assert!(!find("package.json").unwrap().cleans());
+    }
+
+    #[test]
+    fn tools_of_wrappers_and_scripts() {
+        assert_eq!(tool("./gradlew"), "gradle");
+        assert_eq!(tool("gradlew.bat"), "gradle");
+        assert_eq!(tool("mvn.cmd"), "mvn");
+        assert_eq!(tool("cargo"), "cargo");
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{cmd::Cmd, conf::Plan, exec::Execution};
+
+/// Toolchain caches under the home directory, cargo registry is resolved by `CARGO_HOME`.
+const CACHES: [(&str, Layout); 5] = [
+    (".m2/repository", Layout::Maven),
+    (".gradle/caches", Layout::Gradle),
+    (".npm", Layout::Files),
+    (".cache/pip", Layout::Files),
+    ("Library/Caches/pip", Layout::Files),
+];
+
+/// How a cache is split into the entries pruned as a whole, so that a crate or
+/// an artifact version is never left half removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Layout {
+    /// Entries are `<cache|src|index>/<registry>/<crate>`.
+    Cargo,
+    /// Entries are the artifact versions, which hold the `.pom` files.
+    Maven,
+    /// Entries are `modules-2/files-2.1/<group>/<artifact>/<version>`, or the
+    /// directories two levels below the cache otherwise, e.g: `transforms-3/<hash>`.
+    Gradle,
+    /// Entries are the files only, e.g: the content addressed npm and pip caches.
+    Files,
+}
+
+impl Layout {
+    /// Whether the directory at the `path` under the cache `root` is an entry.
+    pub fn entry(&self, root: &Path, path: &Path) -> bool {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let depth = relative.components().count();
+        return match self {
+            Layout::Cargo => depth == 3,
+            Layout::Maven => fs::read_dir(path).is_ok_and(|mut entries| {
+                entries.any(|entry| {
+                    entry.is_ok_and(|entry| entry.file_name().to_string_lossy().ends_with(".pom"))
+                })
+            }),
+            Layout::Gradle if relative.starts_with("modules-2/files-2.1") => depth == 5,
+            Layout::Gradle => depth == 2,
+            Layout::Files => false,
+        };
+    }
+}
+
+/// Discovers the toolchain caches that exist on this machine.
+pub(crate) fn discover() -> Vec<(PathBuf, Layout)> {
+    let cargo = home::cargo_home()
+        .ok()
+        .map(|cargo| (cargo.join("registry"), Layout::Cargo));
+    let home = home::home_dir();
+    let caches = CACHES
+        .iter()
+        .filter_map(|(cache, layout)| home.as_ref().map(|home| (home.join(cache), *layout)));
+    return cargo
+        .into_iter()
+        .chain(caches)
+        .filter(|(cache, _)| cache.is_dir())
+        .collect();
+}
+
+/// Prunes the entries of the `cache` which are older than `older_than`, or all
+/// of the content if `older_than` is absent.
+pub(crate) fn prune<P: AsRef<Path>>(
+    cache: P,
+    layout: Layout,
+    older_than: Option<Duration>,
+) -> Execution<'static> {
+    let cache = cache.as_ref();
+    let before = older_than.map(|age| {
+        let now = SystemTime::now();
+        now.checked_sub(age).unwrap_or(SystemTime::UNIX_EPOCH)
+    });
+    let mut prune = Execution::new(Plan::Prune(before, layout), cache.to_owned());
+    prune.measured = vec![cache.to_owned()];
+    return prune;
+}
+
+/// Cleans the shared go build, test and module caches, which `go clean` on
+/// each module leaves untouched. Nothing is measured if go is not available.
+pub(crate) async fn go<P: AsRef<Path>>(work_dir: P) -> Execution<'static> {
+    let work_dir = work_dir.as_ref();
+    let env = Cmd::new("go", ["env", "GOCACHE", "GOMODCACHE"])
+        .output(work_dir)
+        .await;
+    let mut go = Execution::new(
+        Plan::Cmd(Cmd::new(
+            "go",
+            ["clean", "-cache", "-testcache", "-modcache"],
+        )),
+        work_dir.to_owned(),
+    );
+    go.measured = env
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect();
+    return go;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::Layout;
+    use crate::test::{temp_dir, RmDirGuard};
+
+    #[test]
+    fn entries_of_layouts() {
+        let root = Path::new("/cache");
+        let entry = |layout: Layout, path: &str| layout.entry(root, &root.join(path));
+
+        assert!(entry(
+            Layout::Cargo,
+            "src/index.crates.io-6f17d22bba15001f/serde-1.0.0"
+        ));
+        assert!(!entry(
+            Layout::Cargo,
+            "src/index.crates.io-6f17d22bba15001f"
+        ));
+        assert!(entry(
+            Layout::Gradle,
+            "modules-2/files-2.1/org.slf4j/slf4j-api/2.0.9"
+        ));
+        assert!(!entry(Layout::Gradle, "modules-2/files-2.1/org.slf4j"));
+        assert!(entry(Layout::Gradle, "transforms-3/0a1b2c"));
+        assert!(!entry(Layout::Files, "_cacache/content-v2"));
+
+        let test = temp_dir("test-maven");
+        let _guard = RmDirGuard(&test);
+        std::fs::create_dir_all(test.join("org/slf4j/slf4j-api/2.0.9")).unwrap();
+        std::fs::write(
+            test.join("org/slf4j/slf4j-api/2.0.9/slf4j-api-2.0.9.pom"),
+            "",
+        )
+        .unwrap();
+        assert!(Layout::Maven.entry(&test, &test.join("org/slf4j/slf4j-api/2.0.9")));
+        assert!(!Layout::Maven.entry(&test, &test.join("org/slf4j/slf4j-api")));
+    }
+}
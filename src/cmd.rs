@@ -1,13 +1,17 @@
-use std::{borrow::Cow, path::Path, process::ExitStatus, str::FromStr};
+use std::{borrow::Cow, path::Path, process::ExitStatus, str::FromStr, time::Duration};
 
 use tokio::process::{Child, Command};
 
-use crate::{Error, Result};
+use crate::{builtin, Error, Result};
 
 #[derive(Debug, Clone)]
 pub struct Cmd<'a> {
     pub command: Cow<'a, str>,
     pub args: Vec<Cow<'a, str>>,
+    /// Environment variables set for the command besides the inherited ones.
+    pub env: Vec<(String, String)>,
+    /// The command is killed if it doesn't finish within this duration.
+    pub timeout: Option<Duration>,
 }
 
 impl<'a> Cmd<'a> {
@@ -20,29 +24,83 @@ impl<'a> Cmd<'a> {
         Cmd {
             command: Cow::from(command),
             args: args.into_iter().map(Cow::from).collect(),
+            env: vec![],
+            timeout: None,
         }
     }
 
+    /// Build outputs relative to the work dir that the command cleans, `None` if unknown.
+    pub fn outputs(&self) -> Option<&'static [&'static str]> {
+        let tool = builtin::tool(&self.command);
+        return builtin::all()
+            .filter(|builtin| !builtin.outputs.is_empty())
+            .find(|builtin| builtin.clean.first().map(|cmd| builtin::tool(cmd)) == Some(tool))
+            .map(|builtin| builtin.outputs);
+    }
+
     pub async fn run<P>(&self, work_dir: P) -> Result<ExitStatus>
     where
         P: AsRef<Path>,
     {
-        Ok(self.execute(work_dir).await?.wait().await?)
+        let mut child = self.execute(work_dir).await?;
+        let Some(timeout) = self.timeout else {
+            return Ok(child.wait().await?);
+        };
+        match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(status) => Ok(status?),
+            Err(_) => {
+                // the whole process group, including the processes spawned by
+                // the command, e.g: the compilers spawned by `make`
+                #[cfg(unix)]
+                if let Some(pid) = child.id() {
+                    // SAFETY: kill has no memory effects, the group is the child's own
+                    unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) };
+                }
+                child.kill().await?;
+                Err(Error::other(format!(
+                    "`{}` timed out after {timeout:?}",
+                    self.command
+                )))?
+            }
+        }
+    }
+
+    pub async fn output<P>(&self, work_dir: P) -> Result<String>
+    where
+        P: AsRef<Path>,
+    {
+        let output = self.execute(work_dir).await?.wait_with_output().await?;
+        if !output.status.success() {
+            return Err(Error::other(String::from_utf8_lossy(&output.stderr).trim()))?;
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
     }
 
     #[inline]
     async fn execute<P: AsRef<Path>>(&self, work_dir: P) -> Result<Child> {
-        let mut cmd = Command::new(self.command.as_ref());
-        let cmd = cmd
-            .args(self.args.iter().map(|arg| arg.as_ref()))
+        let mut cmd = std::process::Command::new(self.command.as_ref());
+        cmd.args(self.args.iter().map(|arg| arg.as_ref()))
+            .envs(self.env.iter().map(|(key, value)| (key, value)))
             .current_dir(work_dir.as_ref());
 
-        let cmd = {
+        {
             use std::process::Stdio;
-            cmd.stdout(Stdio::piped()).stderr(Stdio::piped())
-        };
+            cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+
+        // the command of a timeout runs in its own process group to be killed
+        // as a whole
+        #[cfg(unix)]
+        if self.timeout.is_some() {
+            std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+        }
 
-        Ok(cmd.spawn()?)
+        return match Command::from(cmd).spawn() {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                Err(Error::NotFound(self.command.to_string()))?
+            }
+            child => Ok(child?),
+        };
     }
 }
 
@@ -55,27 +113,15 @@ impl<'a> FromStr for Cmd<'a> {
             return Ok(Cmd::new(parts.next().unwrap(), parts));
         }
 
-        macro_rules! resolve {
-            ( $($(#[$meta:meta])? ( $file:literal, $($tt:tt)* )),* $(,)? ) => {
-                match command {
-                    $( $(#[$meta])? $file => Ok(Cmd::new(stringify!($($tt)*), ["clean"])),)*
-                    _ => Err(Error::other(format!("command can not be resolved: `{command}`")))?,
-                }
-            };
+        match builtin::find(command) {
+            Some(builtin) if !builtin.clean.is_empty() => {
+                let (cmd, args) = builtin.clean.split_first().unwrap();
+                Ok(Cmd::new(*cmd, args.iter().copied()))
+            }
+            _ => Err(Error::other(format!(
+                "command can not be resolved: `{command}`"
+            )))?,
         }
-
-        resolve!(
-            ("Cargo.toml", cargo),
-            ("go.mod", go),
-            #[cfg(not(target_os = "windows"))]
-            ("pom.xml", mvn),
-            #[cfg(not(target_os = "windows"))]
-            ("build.gradle", gradle),
-            #[cfg(target_os = "windows")]
-            ("pom.xml", mvn.cmd),
-            #[cfg(any(target_os = "windows"))]
-            ("build.gradle", gradle.bat),
-        )
     }
 }
 
@@ -106,6 +152,50 @@ mod tests {
         assert_eq!(String::from_utf8(out.stdout.clone()).unwrap(), "/home\n");
     }
 
+    #[tokio::test]
+    #[cfg(target_os = "linux")]
+    async fn output() {
+        let echo = Cmd::new("echo", ["hello"]);
+        assert_eq!(echo.output(".").await.unwrap(), "hello\n");
+
+        let ls = Cmd::new("ls", ["absent"]);
+        assert!(ls.output(".").await.is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(target_os = "linux")]
+    async fn run_with_env_and_timeout() {
+        let mut test = Cmd::new("sh", ["-c", "test \"$MODE\" = release"]);
+        assert!(!test.run(".").await.unwrap().success());
+        test.env = vec![("MODE".into(), "release".into())];
+        assert!(test.run(".").await.unwrap().success());
+
+        let mut sleep = Cmd::new("sleep", ["5"]);
+        sleep.timeout = Some(std::time::Duration::from_millis(100));
+        let err = sleep.run(".").await.unwrap_err();
+        assert_eq!(err.to_string(), "`sleep` timed out after 100ms");
+    }
+
+    #[tokio::test]
+    #[cfg(target_os = "linux")]
+    async fn kill_process_group_on_timeout() {
+        let test = crate::test::temp_dir("test-process-group");
+        let _guard = crate::test::RmDirGuard(&test);
+        std::fs::create_dir_all(&test).unwrap();
+        let mut sh = Cmd::new("sh", ["-c", "sleep 5 & echo $! > pid; wait"]);
+        sh.timeout = Some(std::time::Duration::from_millis(200));
+        assert!(sh.run(&test).await.is_err());
+
+        let pid = std::fs::read_to_string(test.join("pid")).unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        // the killed orphan is gone, or a zombie left to be reaped
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()));
+        assert!(
+            stat.map_or(true, |stat| stat.contains(") Z ")),
+            "sleep is killed"
+        );
+    }
+
     #[test]
     #[cfg(not(target_os = "windows"))]
     fn builtin_commands() {
@@ -114,6 +204,12 @@ mod tests {
             ("go.mod", "go"),
             ("pom.xml", "mvn"),
             ("build.gradle", "gradle"),
+            ("WORKSPACE", "bazel"),
+            ("MODULE.bazel", "bazel"),
+            ("Makefile", "make"),
+            ("App.sln", "dotnet"),
+            ("App.csproj", "dotnet"),
+            ("pubspec.yaml", "flutter"),
         ];
         for (file, expected) in tests {
             let cmd = file.parse::<Cmd>().unwrap();
@@ -122,6 +218,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn builtin_commands_with_subcommand() {
+        let swift = "Package.swift".parse::<Cmd>().unwrap();
+        assert_eq!(swift.command, "swift");
+        assert_eq!(swift.args, ["package", "clean"]);
+    }
+
     #[test]
     #[cfg(target_os = "windows")]
     fn builtin_commands_on_windows() {
@@ -138,6 +241,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn builtin_command_outputs() {
+        let cargo = "Cargo.toml".parse::<Cmd>().unwrap();
+        assert_eq!(cargo.outputs().unwrap(), ["target"]);
+
+        let mvn = "!mvn -B clean".parse::<Cmd>().unwrap();
+        assert_eq!(mvn.outputs().unwrap(), ["target"]);
+
+        let gradlew = "!./gradlew clean".parse::<Cmd>().unwrap();
+        assert_eq!(gradlew.outputs().unwrap(), ["build"]);
+
+        let go = "go.mod".parse::<Cmd>().unwrap();
+        assert!(go.outputs().is_none());
+    }
+
     #[test]
     fn custom_commands() {
         let rm = "!rm -rf .".parse::<Cmd>().unwrap();
@@ -148,7 +266,12 @@ mod tests {
     #[test]
     fn fails_on_parse_invalid_command() {
         let err = "test".parse::<Cmd>().unwrap_err();
-
         assert_eq!(err.to_string(), "command can not be resolved: `test`");
+
+        let err = "package.json".parse::<Cmd>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "command can not be resolved: `package.json`"
+        );
     }
 }
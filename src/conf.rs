@@ -1,37 +1,238 @@
-use std::{borrow::Cow, collections::HashMap, ffi::OsString, path::Path};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
 
+use futures::future::BoxFuture;
 use tokio::{
     fs::File,
     io::{AsyncBufReadExt, AsyncRead, BufReader},
 };
 
-use crate::{cmd::Cmd, Error, Result};
+use crate::{
+    builtin,
+    cache::Layout,
+    cmd::Cmd,
+    disk,
+    opts::Options,
+    trash::{self, Trash},
+    Error, Result,
+};
 
 #[derive(Debug, Clone)]
 pub(crate) enum Plan<'a> {
     Cmd(Cmd<'a>),
     RmDir(OsString),
+    /// Removes the files matching the pattern in the work dir, but not the directories.
+    RmFiles(glob::Pattern),
+    /// Packs the directory into a gzipped tar archive under the given
+    /// directory, and then removes it.
+    Archive(OsString, PathBuf),
+    /// Removes the entries of the cache in the work dir not modified since the
+    /// given time, or all of the content if the time is absent.
+    Prune(Option<SystemTime>, Layout),
+    /// Removes the entries moved into the trash before the given time, or all
+    /// of the entries if the time is absent.
+    Purge(Trash, Option<SystemTime>),
+    /// Moves the quarantined directory back to the work dir, which must not exist.
+    Restore(PathBuf),
+    /// Runs the plans in order, a plan flagged as chained runs only if the
+    /// previous one succeeded, like `&&` rather than `;` in the shell.
+    All(Vec<(Plan<'a>, bool)>),
 }
 
 impl<'a> Plan<'a> {
-    pub async fn run<P: AsRef<Path>>(&self, work_dir: P) -> Result<bool> {
-        let work_dir = work_dir.as_ref();
-        match self {
-            Plan::Cmd(cmd) if work_dir.exists() => {
-                Ok(cmd.run(work_dir).await.map(|status| status.success())?)
+    /// Runs the plan in the `work_dir`, the removed directories are moved into
+    /// the `trash` if present.
+    pub async fn run<P>(&self, work_dir: P, trash: Option<&Trash>) -> Result<bool>
+    where
+        P: AsRef<Path>,
+    {
+        return self.run_boxed(work_dir.as_ref(), trash).await;
+    }
+
+    /// Runs the plan boxed, which the composed plans recurse into.
+    fn run_boxed<'b>(
+        &'b self,
+        work_dir: &'b Path,
+        trash: Option<&'b Trash>,
+    ) -> BoxFuture<'b, Result<bool>> {
+        return Box::pin(async move {
+            match self {
+                Plan::Cmd(cmd) if work_dir.exists() => {
+                    Ok(cmd.run(work_dir).await.map(|status| status.success())?)
+                }
+                Plan::RmDir(dir) => match resolve(work_dir, dir)? {
+                    Some(path) => remove(&path, trash),
+                    None => Ok(true),
+                },
+                Plan::Archive(dir, archives) => match resolve(work_dir, dir)? {
+                    Some(path) => {
+                        let project = normalize(work_dir);
+                        // the projects of the same name are told apart by their paths
+                        let hash = {
+                            use std::hash::{Hash, Hasher};
+                            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                            project.hash(&mut hasher);
+                            hasher.finish() as u32
+                        };
+                        let name = format!(
+                            "{}-{}-{}-{hash:08x}.tar.gz",
+                            project.file_name().unwrap_or_default().to_string_lossy(),
+                            dir.to_string_lossy().replace(['/', '\\'], "_"),
+                            trash::timestamp(SystemTime::now()).replace(['-', ':'], "")
+                        );
+                        let (src, name, archive) =
+                            (path.clone(), PathBuf::from(dir), archives.join(name));
+                        tokio::task::spawn_blocking(move || disk::archive(src, &name, &archive))
+                            .await??;
+                        remove(&path, trash)
+                    }
+                    None => Ok(true),
+                },
+                Plan::RmFiles(_) => {
+                    for file in self.outputs(work_dir).unwrap_or_default() {
+                        let file = work_dir.join(file);
+                        match trash {
+                            Some(trash) => trash.dispose(&file)?,
+                            None => std::fs::remove_file(file)?,
+                        }
+                    }
+                    Ok(true)
+                }
+                Plan::Prune(before, layout) if work_dir.is_dir() => {
+                    let (dir, before, layout) = (work_dir.to_owned(), *before, *layout);
+                    let entry = move |path: &Path| layout.entry(&dir, path);
+                    let dir = work_dir.to_owned();
+                    tokio::task::spawn_blocking(move || disk::prune(dir, before, &entry)).await??;
+                    Ok(true)
+                }
+                Plan::Purge(trash, before) => {
+                    let (trash, before) = (trash.clone(), *before);
+                    tokio::task::spawn_blocking(move || trash.purge(before)).await??;
+                    Ok(true)
+                }
+                Plan::Restore(_) if work_dir.symlink_metadata().is_ok() => Err(Error::other(
+                    format!("{} already exists", work_dir.display()),
+                ))?,
+                Plan::Restore(quarantined) => {
+                    if let Some(parent) = work_dir.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    trash::rename(quarantined, work_dir)?;
+                    Ok(true)
+                }
+                Plan::All(plans) => {
+                    // an error fails the plan like an unsuccessful status, the
+                    // first one is returned once the rest have run
+                    let (mut clean, mut ok, mut error) = (true, true, None);
+                    for (plan, chained) in plans {
+                        if *chained && !ok {
+                            clean = false;
+                            continue;
+                        }
+                        ok = match plan.run_boxed(work_dir, trash).await {
+                            Ok(ok) => ok,
+                            Err(err) => {
+                                error = error.or(Some(err));
+                                false
+                            }
+                        };
+                        clean = clean && ok;
+                    }
+                    error.map_or(Ok(clean), Err)
+                }
+                _ => Ok(true),
+            }
+        });
+
+        /// The directory to remove, `None` if it's absent, a symlink is removed
+        /// itself rather than the target.
+        fn resolve(work_dir: &Path, dir: &OsStr) -> Result<Option<PathBuf>> {
+            match work_dir.join(dir) {
+                path if path.symlink_metadata().is_err() => Ok(None),
+                path if path.is_symlink() => Ok(Some(path)),
+                path if !path.canonicalize()?.starts_with(work_dir.canonicalize()?) => Err(
+                    Error::other(format!("{} escapes {}", path.display(), work_dir.display())),
+                )?,
+                path => Ok(Some(path)),
+            }
+        }
+
+        fn remove(path: &Path, trash: Option<&Trash>) -> Result<bool> {
+            match trash {
+                Some(trash) => Ok(trash.dispose(path).map(|_| true)?),
+                None if path.is_symlink() => Ok(std::fs::remove_file(path)
+                    .or_else(|_| std::fs::remove_dir(path))
+                    .map(|_| true)?),
+                None => Ok(remove_dir_all::remove_dir_all(path).map(|_| true)?),
             }
-            Plan::RmDir(dir) => match work_dir.join(dir) {
-                path if !path.exists() => Ok(true),
-                path => Ok(remove_dir_all::remove_dir_all(path).map(|_| true)?),
-            },
-            _ => Ok(true),
         }
     }
 
-    pub fn cmd(&self) -> &Cow<str> {
+    pub fn cmd(&self) -> &Cow<'_, str> {
         match self {
             Plan::Cmd(cmd) => &cmd.command,
-            Plan::RmDir(_) => &Cow::Borrowed("rm"),
+            Plan::RmDir(_) | Plan::RmFiles(_) => &Cow::Borrowed("rm"),
+            Plan::Archive(..) => &Cow::Borrowed("archive"),
+            Plan::Prune(..) => &Cow::Borrowed("prune"),
+            Plan::Purge(..) => &Cow::Borrowed("purge"),
+            Plan::Restore(_) => &Cow::Borrowed("restore"),
+            Plan::All(plans) => plans
+                .first()
+                .map_or(&Cow::Borrowed("all"), |(plan, _)| plan.cmd()),
+        }
+    }
+
+    /// Paths relative to the `work_dir` that the plan cleans, `None` if unknown.
+    pub fn outputs<P: AsRef<Path>>(&self, work_dir: P) -> Option<Vec<PathBuf>> {
+        match self {
+            Plan::Cmd(cmd) => Some(cmd.outputs()?.iter().map(PathBuf::from).collect()),
+            Plan::RmFiles(pattern) => {
+                let entries = std::fs::read_dir(work_dir).ok()?.flatten();
+                let files = entries
+                    .filter(|entry| entry.file_type().is_ok_and(|file| !file.is_dir()))
+                    .map(|entry| PathBuf::from(entry.file_name()))
+                    .filter(|file| {
+                        file.to_str().is_some_and(|file| {
+                            pattern.matches(file) && builtin::find(file).is_none()
+                        })
+                    });
+                Some(files.collect())
+            }
+            Plan::RmDir(dir) | Plan::Archive(dir, _) => Some(vec![dir.into()]),
+            Plan::Prune(..) | Plan::Purge(..) => Some(vec![PathBuf::new()]),
+            Plan::Restore(_) => None,
+            Plan::All(plans) => {
+                let outputs = plans
+                    .iter()
+                    .filter_map(|(plan, _)| plan.outputs(work_dir.as_ref()));
+                Some(outputs.flatten().collect::<Vec<_>>()).filter(|outputs| !outputs.is_empty())
+            }
+        }
+    }
+
+    /// Appends the `next` plan to run after this one, only if this one succeeded
+    /// if `chained`.
+    pub fn then(self, next: Plan<'a>, chained: bool) -> Plan<'a> {
+        match self {
+            Plan::All(mut plans) => {
+                plans.push((next, chained));
+                Plan::All(plans)
+            }
+            plan => Plan::All(vec![(plan, false), (next, chained)]),
+        }
+    }
+
+    /// The directories relative to the work dir that the plan removes.
+    pub fn removes(&self) -> Vec<&OsString> {
+        match self {
+            Plan::RmDir(dir) | Plan::Archive(dir, _) => vec![dir],
+            Plan::All(plans) => plans.iter().flat_map(|(plan, _)| plan.removes()).collect(),
+            _ => vec![],
         }
     }
 
@@ -39,105 +240,658 @@ impl<'a> Plan<'a> {
     fn into_cmd(self) -> Option<Cmd<'a>> {
         match self {
             Plan::Cmd(cmd) => Some(cmd),
-            Plan::RmDir(_) => None,
+            _ => None,
         }
     }
 
     fn filter<P: AsRef<Path>>(self, path: P) -> Option<Self> {
-        match self {
-            Plan::RmDir(_) if !path.as_ref().is_dir() => None,
+        let first = match &self {
+            Plan::All(plans) => plans.first().map(|(plan, _)| plan),
+            plan => Some(plan),
+        };
+        match first {
+            Some(Plan::RmDir(_) | Plan::Archive(..)) if !path.as_ref().is_dir() => None,
             _ => Some(self),
         }
     }
 }
 
-type Registry = Box<dyn Fn() -> Plan<'static>>;
+/// A rule of the `.cleanrc`, which applies only if its conditions hold.
+#[derive(Debug, Clone)]
+struct Rule {
+    plan: Plan<'static>,
+    /// Globs of the sibling files, which must exist, or must not if `false`,
+    /// e.g: `if package.json`, `unless pom.xml`.
+    conditions: Vec<(glob::Pattern, bool)>,
+    /// The ecosystem selected by `--only` and `--skip` besides the built-in one.
+    ecosystem: Option<String>,
+}
+
+impl Rule {
+    fn applies(&self, dir: &Path) -> bool {
+        return self
+            .conditions
+            .iter()
+            .all(|(sibling, exists)| has_sibling(dir, |name| sibling.matches(name)) == *exists);
+    }
+}
 
 #[derive(Default)]
 pub struct Config {
-    registry: HashMap<String, Registry>,
+    registry: HashMap<String, Vec<Rule>>,
+    /// Globs of the files to remove, e.g: `*.log`.
+    files: Vec<(glob::Pattern, Rule)>,
+    /// Paths never cleaned, e.g: `!~/work/release`.
+    protected: Vec<PathBuf>,
+    /// Names of the disabled built-in rules, e.g: `disable go`, or `disable *` for all.
+    disabled: Vec<String>,
+    /// Warnings of loading the config, which the caller reports.
+    warnings: Vec<String>,
+    pub options: Options,
 }
 
-unsafe impl Send for Config {}
-unsafe impl Sync for Config {}
-
 impl Config {
     pub fn empty() -> Config {
         Default::default()
     }
 
+    /// Loads the `~/.cleanrc.toml`, or the `~/.cleanrc` in the line format if
+    /// absent, which is ignored with a warning if both exist.
     pub async fn home() -> Result<Config> {
-        match home::home_dir().map(|home| home.join(".cleanrc")) {
-            Some(file) if file.is_file() => Self::load(File::open(file).await?).await,
+        let Some(home) = home::home_dir() else {
+            return Ok(Self::empty());
+        };
+        match (home.join(".cleanrc.toml"), home.join(".cleanrc")) {
+            (toml, file) if toml.is_file() => {
+                let mut config = Self::load_toml(File::open(&toml).await?).await?;
+                if file.is_file() {
+                    config.warnings.push(format!(
+                        "{} is ignored, as {} takes precedence",
+                        file.display(),
+                        toml.display()
+                    ));
+                }
+                Ok(config)
+            }
+            (_, file) if file.is_file() => Self::load(File::open(file).await?).await,
             _ => Ok(Self::empty()),
         }
     }
 
     pub async fn load<T: AsyncRead + Unpin>(config: T) -> Result<Config> {
         let mut config = BufReader::new(config).lines();
-        let mut registry = HashMap::<String, Registry>::new();
+        let mut registry = HashMap::<String, Vec<Rule>>::new();
+        let mut protected = vec![];
+        let mut files = vec![];
+        let mut disabled = vec![];
         while let Some(line) = config.next_line().await? {
             let line = line.trim();
             if line.is_empty() || line.starts_with('#') {
                 continue;
             }
 
-            if let Some(dir) = line.strip_suffix('/') {
-                let dir = dir.to_string();
-                registry.insert(
-                    dir.to_string(),
-                    Box::new(move || Plan::RmDir(dir.clone().into())),
+            if let Some(path) = line.strip_prefix('!').map(str::trim) {
+                match expand(path) {
+                    Some(path) if path.is_absolute() => protected.push(normalize(path)),
+                    _ => return Err(help())?,
+                }
+                continue;
+            }
+
+            // a rule of the file named `disable` has the `=` after the name
+            if let Some(names) = line
+                .strip_prefix("disable ")
+                .filter(|names| !names.contains('='))
+            {
+                for name in names.split_whitespace() {
+                    match is_builtin(name) {
+                        true => disabled.push(name.to_string()),
+                        false => return Err(help())?,
+                    }
+                }
+                continue;
+            }
+
+            let (line, conditions) = conditions(line)?;
+            let line = line.as_str();
+            let rule = |plan| Rule {
+                plan,
+                conditions: conditions.clone(),
+                ecosystem: None,
+            };
+
+            if let Some((dir, archives)) = line
+                .split_once("=>")
+                .and_then(|(dir, archives)| Some((dir.trim().strip_suffix('/')?, archives)))
+            {
+                let dir = valid(dir.trim())?;
+                let archives = match expand(archives.trim()) {
+                    Some(archives) if archives.is_absolute() => archives,
+                    _ => return Err(help())?,
+                };
+                register(
+                    &mut registry,
+                    dir,
+                    rule(Plan::Archive(dir.into(), archives)),
                 );
                 continue;
             }
 
+            if let Some(dir) = line.strip_suffix('/').filter(|_| !line.contains('=')) {
+                let dir = valid(dir.trim())?;
+                register(&mut registry, dir, rule(Plan::RmDir(dir.into())));
+                continue;
+            }
+
+            if !line.contains('=') && line.contains(['*', '?', '[']) {
+                let pattern = match valid(line)? {
+                    line if line.contains(['/', '\\']) => Err(help())?,
+                    line => glob::Pattern::new(line).map_err(|_| help())?,
+                };
+                files.push((pattern.clone(), rule(Plan::RmFiles(pattern))));
+                continue;
+            }
+
             let mut parts = line.splitn(2, '=').map(|s| s.trim());
             match (parts.next(), parts.next()) {
+                // the rules are looked up by the file name, never by a glob
+                (Some(file), _) if file.contains(['*', '?', '[']) => return Err(help())?,
                 (Some(file), Some(cmd)) if !file.is_empty() && !cmd.is_empty() => {
-                    let cmd = format!("!{cmd}").parse::<Cmd>().map_err(|_| help())?;
-                    registry.insert(file.to_string(), Box::new(move || Plan::Cmd(cmd.clone())));
+                    register(&mut registry, valid(file)?, rule(actions(cmd)?));
                 }
                 _ => return Err(help())?,
             }
         }
 
-        return Ok(Config { registry });
+        return Ok(Config {
+            registry,
+            files,
+            protected,
+            disabled,
+            ..Default::default()
+        });
+
+        /// Splits the `if <glob>` and `unless <glob>` conditions off the rule name,
+        /// i.e. before the `=` or `=>` if any, so the arguments of the commands
+        /// are never taken as conditions, e.g: `pom.xml unless mvnw = mvn clean`.
+        fn conditions(line: &str) -> Result<(String, Vec<(glob::Pattern, bool)>)> {
+            let (name, rest) = line.split_at(line.find('=').unwrap_or(line.len()));
+            let (mut name, mut conditions) = (name.trim_end(), vec![]);
+            while let Some((rest, sibling)) = name.rsplit_once(char::is_whitespace) {
+                let rest = rest.trim_end();
+                let (rest, keyword) = rest.rsplit_once(char::is_whitespace).unwrap_or(("", rest));
+                let exists = match keyword {
+                    "if" => true,
+                    "unless" => false,
+                    _ => break,
+                };
+                let sibling = glob::Pattern::new(sibling).map_err(|_| help())?;
+                conditions.push((sibling, exists));
+                name = rest.trim_end();
+            }
+            return match rest.is_empty() {
+                true => Ok((name.to_string(), conditions)),
+                false => Ok((format!("{name} {rest}"), conditions)),
+            };
+        }
+    }
+
+    /// Loads the config in TOML, whose rules map onto the line format, but are
+    /// able to run the commands with a timeout and environment variables, and
+    /// to take a priority and an ecosystem, e.g:
+    ///
+    /// ```toml
+    /// protected = ["~/work/release"]
+    /// disabled = ["go"]
+    ///
+    /// [[rules]]
+    /// name = "pom.xml"
+    /// run = "mvn -B clean && .mvn/"
+    /// timeout = 300
+    /// env = { MAVEN_OPTS = "-Xmx1g" }
+    /// unless = ["build.gradle"]
+    /// ecosystem = "java"
+    /// ```
+    pub async fn load_toml<T: AsyncRead + Unpin>(mut config: T) -> Result<Config> {
+        use serde::Deserialize;
+        use tokio::io::AsyncReadExt;
+
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Schema {
+            /// Paths never cleaned, like `!~/work/release`.
+            #[serde(default)]
+            protected: Vec<String>,
+            /// Names of the disabled built-in rules, like `disable go`.
+            #[serde(default)]
+            disabled: Vec<String>,
+            #[serde(default)]
+            rules: Vec<RuleSchema>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct RuleSchema {
+            /// The file to run the actions for, the `dir/` to remove, or the
+            /// glob of the files to remove.
+            name: String,
+            /// Actions of the file, like `pom.xml = <actions>`.
+            run: Option<String>,
+            /// Directory to archive the `dir/` into, like `target/ => <archive>`.
+            archive: Option<String>,
+            /// Globs of the sibling files which must exist.
+            #[serde(default, rename = "if")]
+            exists: Vec<String>,
+            /// Globs of the sibling files which must not exist.
+            #[serde(default)]
+            unless: Vec<String>,
+            /// Seconds the commands are killed after, of the rule to `run` only.
+            timeout: Option<u64>,
+            /// Environment variables of the commands, of the rule to `run` only.
+            #[serde(default)]
+            env: BTreeMap<String, String>,
+            /// Rules of the same name run from the highest priority, and the
+            /// file globs are tried from the highest priority, 0 by default.
+            #[serde(default)]
+            priority: i64,
+            ecosystem: Option<String>,
+        }
+
+        let mut content = String::new();
+        config.read_to_string(&mut content).await?;
+        let mut schema = toml::from_str::<Schema>(&content)?;
+        let mut config = Config::empty();
+        for path in schema.protected {
+            match expand(&path) {
+                Some(absolute) if absolute.is_absolute() => config.protect(absolute),
+                _ => Err(Error::other(format!(
+                    "protected path is not absolute: `{path}`"
+                )))?,
+            }
+        }
+        for name in schema.disabled {
+            match is_builtin(&name) {
+                true => config.disabled.push(name),
+                _ => Err(Error::other(format!(
+                    "no built-in rule of the name: `{name}`"
+                )))?,
+            }
+        }
+
+        schema
+            .rules
+            .sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+        for rule in schema.rules {
+            let name = rule.name.trim();
+            let siblings = rule.exists.iter().map(|sibling| (sibling, true));
+            let siblings = siblings.chain(rule.unless.iter().map(|sibling| (sibling, false)));
+            let conditions = siblings
+                .map(|(sibling, exists)| Ok((pattern(sibling)?, exists)))
+                .collect::<Result<Vec<_>>>()?;
+            if rule.run.is_none() && (rule.timeout.is_some() || !rule.env.is_empty()) {
+                Err(Error::other(format!(
+                    "timeout and env apply to the rule to run only: `{name}`"
+                )))?
+            }
+            let env = rule.env.into_iter().collect::<Vec<_>>();
+            let timeout = rule.timeout.map(Duration::from_secs);
+            let plan = match (name.strip_suffix('/'), &rule.run, &rule.archive) {
+                (Some(dir), None, None) => Plan::RmDir(valid(dir)?.into()),
+                (Some(dir), None, Some(archives)) => match expand(archives) {
+                    Some(archives) if archives.is_absolute() => {
+                        Plan::Archive(valid(dir)?.into(), archives)
+                    }
+                    _ => Err(Error::other(format!(
+                        "archive is not absolute: `{archives}`"
+                    )))?,
+                },
+                (None, None, None) if name.contains(['*', '?', '[']) => match valid(name)? {
+                    name if name.contains(['/', '\\']) => {
+                        Err(Error::other(format!("file glob has separators: `{name}`")))?
+                    }
+                    name => Plan::RmFiles(pattern(name)?),
+                },
+                (None, Some(_), None) if name.contains(['*', '?', '[']) => Err(Error::other(
+                    format!("file glob can not run actions: `{name}`"),
+                ))?,
+                (None, Some(run), None) => match valid(name).and(actions(run)) {
+                    Ok(plan) => configure(plan, &env, timeout),
+                    _ => Err(Error::other(format!(
+                        "invalid actions of `{name}`: `{run}`"
+                    )))?,
+                },
+                _ => Err(Error::other(format!(
+                    "invalid rule `{name}`: expect a `dir/`, a file glob or a file to run"
+                )))?,
+            };
+            let rule = Rule {
+                plan,
+                conditions,
+                ecosystem: rule.ecosystem,
+            };
+            match &rule.plan {
+                Plan::RmFiles(pattern) => config.files.push((pattern.clone(), rule)),
+                _ => register(&mut config.registry, name.trim_end_matches('/'), rule),
+            }
+        }
+        return Ok(config);
+
+        fn pattern(glob: &str) -> Result<glob::Pattern> {
+            Ok(glob::Pattern::new(glob)
+                .map_err(|_| Error::other(format!("invalid glob: `{glob}`")))?)
+        }
+
+        /// Runs the commands of the `plan` with the `env` and the `timeout`.
+        fn configure(
+            plan: Plan<'static>,
+            env: &[(String, String)],
+            timeout: Option<Duration>,
+        ) -> Plan<'static> {
+            match plan {
+                Plan::Cmd(cmd) => Plan::Cmd(Cmd {
+                    env: env.to_vec(),
+                    timeout,
+                    ..cmd
+                }),
+                Plan::All(plans) => Plan::All(
+                    plans
+                        .into_iter()
+                        .map(|(plan, chained)| (configure(plan, env, timeout), chained))
+                        .collect(),
+                ),
+                plan => plan,
+            }
+        }
+    }
+
+    /// Warnings of loading the config, e.g: the `~/.cleanrc` is ignored.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Protects the `path` from cleaning.
+    pub(crate) fn protect<P: AsRef<Path>>(&mut self, path: P) {
+        self.protected.push(normalize(path));
+    }
+
+    /// Whether the `path` is or is inside a protected path.
+    pub(crate) fn protects<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = normalize(path);
+        return self
+            .protected
+            .iter()
+            .any(|protected| path.starts_with(protected));
+    }
+
+    /// Whether the `path` marks its directory as a project, by a built-in marker
+    /// file or the file of a registered rule, e.g: `Cargo.toml`, `pom.xml`.
+    pub(crate) fn marks<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        return (builtin::find(name).is_some() || self.registry.contains_key(name))
+            && path.is_file();
+    }
+
+    /// Whether the files of the `plan` are removed by a glob rule of conditions,
+    /// which hold wherever the plan is parsed, e.g: `*.pyc if setup.py`.
+    pub(crate) fn conditional(&self, plan: &Plan) -> bool {
+        let Plan::RmFiles(glob) = plan else {
+            return false;
+        };
+        return self
+            .files
+            .iter()
+            .any(|(pattern, rule)| pattern == glob && !rule.conditions.is_empty());
+    }
+
+    pub(crate) fn parse<P: AsRef<Path>>(&self, path: P) -> Option<Plan<'static>> {
+        let path = path.as_ref();
+        let filename = path.file_name()?.to_str()?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let rules = self.registry.get(filename).into_iter().flatten();
+        let rules = rules.filter(|rule| rule.applies(dir)).collect::<Vec<_>>();
+        if !rules.is_empty() {
+            // each rule is selected by its own ecosystem before they are merged
+            let selected = rules
+                .into_iter()
+                .filter(|rule| self.selects(&rule.plan, filename, rule.ecosystem.as_deref()));
+            return selected
+                .map(|rule| rule.plan.clone())
+                .reduce(|plan, next| plan.then(next, false))?
+                .filter(path);
+        }
+        // the built-in markers are never removed by the globs, e.g: `*.toml`
+        let file = self.files.iter().find(|(pattern, rule)| {
+            !path.is_dir()
+                && builtin::find(filename).is_none()
+                && pattern.matches(filename)
+                && rule.applies(dir)
+        });
+        let (plan, rule, ecosystem) = match file {
+            Some((pattern, rule)) => (
+                rule.plan.clone(),
+                pattern.as_str(),
+                rule.ecosystem.as_deref(),
+            ),
+            _ => match builtin(path, filename)? {
+                (plan, rule) if self.disables(&plan, rule) => return None,
+                (plan, rule) => (plan, rule, None),
+            },
+        };
+        return self.selects(&plan, rule, ecosystem).then_some(plan);
+    }
+
+    /// Whether the plan of the `rule` is selected by the `--only` and `--skip`
+    /// options, by the rule name, its ecosystem, the command or the removed dir.
+    fn selects(&self, plan: &Plan, rule: &str, ecosystem: Option<&str>) -> bool {
+        let (only, skip) = (&self.options.only, &self.options.skip);
+        let named = |names| named(plan, rule, ecosystem, names);
+        return (only.is_empty() || named(only)) && !named(skip);
+    }
+
+    /// Whether the built-in plan of the `rule` is disabled, e.g: `disable go`,
+    /// or all of the built-ins by `disable *`.
+    fn disables(&self, plan: &Plan, rule: &str) -> bool {
+        let disabled = &self.disabled;
+        return disabled.iter().any(|name| name == "*") || named(plan, rule, None, disabled);
+    }
+}
+
+/// Registers the `rule` of the `name`, which runs after the rules
+/// registered already.
+fn register(registry: &mut HashMap<String, Vec<Rule>>, name: &str, rule: Rule) {
+    registry.entry(name.to_string()).or_default().push(rule);
+}
+
+/// Parses the actions separated by `&&` or `;`, an action of a single
+/// word ending with slash removes the directory, others run the command.
+///
+/// The separators are never passed to the commands as arguments, which they
+/// used to be before the actions were composed, see the README.
+fn actions(actions: &str) -> Result<Plan<'static>> {
+    let separator = |actions: &str| {
+        let separators = ["&&", ";"].into_iter();
+        separators
+            .filter_map(|sep| actions.find(sep).map(|i| (i, sep)))
+            .min()
+    };
+    let (mut plan, mut rest, mut chained) = (None::<Plan>, actions, false);
+    loop {
+        let (action, next) = match separator(rest) {
+            Some((i, sep)) => (&rest[..i], Some((&rest[i + sep.len()..], sep == "&&"))),
+            None => (rest, None),
+        };
+        let action = match action.trim() {
+            "" => return Err(help())?,
+            dir if dir.ends_with('/') && !dir.contains(char::is_whitespace) => {
+                Plan::RmDir(valid(dir.trim_end_matches('/'))?.into())
+            }
+            cmd => Plan::Cmd(format!("!{cmd}").parse::<Cmd>().map_err(|_| help())?),
+        };
+        plan = Some(match plan {
+            Some(plan) => plan.then(action, chained),
+            None => action,
+        });
+        match next {
+            Some((next, and)) => (rest, chained) = (next, and),
+            None => return Ok(plan.unwrap_or(Plan::All(vec![]))),
+        }
+    }
+}
+
+/// Rule names are relative to the work dir, which never escape it.
+fn valid(name: &str) -> Result<&str> {
+    use std::path::Component;
+    let mut components = Path::new(name).components();
+    if !components.all(|component| matches!(component, Component::Normal(_))) {
+        return Err(Error::other(format!("invalid rule name: `{name}`")).into());
+    }
+    Ok(name)
+}
 
-        fn help() -> Error {
-            Error::other(
-                "\
+fn help() -> Error {
+    Error::other(
+        "\
 # Config Examples
 
 # rm directory recursively
 node_modules/
 
 # run custom command
-pom.xml = mvn -B clean\
+pom.xml = mvn -B clean
+
+# run actions in order, `&&` stops on failure but `;` continues, neither is
+# passed to the command as an argument
+build.gradle = ./gradlew clean && .gradle/ ; buildSrc/build/
+
+# archive directory into ~/archives, and then rm it
+target/ => ~/archives
+
+# rm files matching the glob inside the projects, or where its conditions hold
+*.log
+
+# rm directory only if the sibling file exists, or unless it exists, the
+# conditions follow the rule name, i.e. before `=` or `=>`
+node_modules/ if package.json
+build/ if build.gradle unless CMakeLists.txt
+
+# never clean the path
+!~/work/release
+
+# disable the built-in rules by the file, ecosystem or build dir, or all by `*`
+disable go.mod maven\
 ",
-            )
+    )
+}
+
+/// Whether the plan of the `rule` is any of the `names`, by the rule name, its
+/// built-in or given ecosystem, the command or the removed dir.
+fn named(plan: &Plan, rule: &str, ecosystem: Option<&str>, names: &[String]) -> bool {
+    let dirs = plan.removes().into_iter().filter_map(|dir| dir.to_str());
+    let aliases = [
+        Some(rule),
+        builtin::ecosystem(rule),
+        ecosystem,
+        Some(plan.cmd()),
+    ]
+    .into_iter()
+    .flatten()
+    .chain(dirs)
+    .collect::<Vec<_>>();
+    return names.iter().any(|name| aliases.contains(&name.as_str()));
+}
+
+/// Whether the `name` refers to any built-in rule, by the marker file, the
+/// ecosystem or the build dir.
+fn is_builtin(name: &str) -> bool {
+    return name == "*"
+        || builtin::all().filter(|each| each.cleans()).any(|each| {
+            builtin::matches(each.marker, name)
+                || each.ecosystem == name
+                || each
+                    .build_dirs
+                    .iter()
+                    .any(|dir| builtin::matches(dir, name))
+        });
+}
+
+/// Expands the leading `~` to the home directory.
+pub(crate) fn expand(path: &str) -> Option<PathBuf> {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            Some(home::home_dir()?.join(rest.trim_start_matches(['/', '\\'])))
         }
+        _ => Some(PathBuf::from(path)),
     }
+}
 
-    pub(crate) fn parse<P: AsRef<Path>>(&self, path: P) -> Option<Plan<'static>> {
-        let path = path.as_ref();
-        let filename = path.file_name()?.to_str()?;
-        match self.registry.get(filename) {
-            Some(registry) => return registry().filter(path),
-            _ => filename.parse().ok().map(Plan::Cmd),
+/// Resolves the `path` to its canonical form if it exists, or an absolute path.
+pub(crate) fn normalize<P: AsRef<Path>>(path: P) -> PathBuf {
+    use path_absolutize::Absolutize;
+    let path = path.as_ref();
+    return path.canonicalize().unwrap_or_else(|_| {
+        path.absolutize()
+            .map_or_else(|_| path.to_owned(), |path| path.into_owned())
+    });
+}
+
+/// Resolves the built-in plan and the rule name which is the marker file.
+fn builtin<'a>(path: &Path, filename: &'a str) -> Option<(Plan<'static>, &'a str)> {
+    if path.is_dir() {
+        let parent = path.parent()?;
+        let build_dir = builtin::all().find(|each| {
+            each.build_dirs
+                .iter()
+                .any(|dir| builtin::matches(dir, filename))
+                && has_sibling(parent, |name| builtin::matches(each.marker, name))
+        });
+        if let Some(each) = build_dir {
+            return Some((Plan::RmDir(filename.into()), each.marker));
         }
     }
+
+    let each = builtin::find(filename)?;
+    if each.ecosystem == "make" && !has_clean_target(path) {
+        return None;
+    }
+    return filename.parse().ok().map(|cmd| (Plan::Cmd(cmd), filename));
 }
 
-#[cfg(test)]
-#[path = "./test.rs"]
-mod test;
+fn has_sibling<F: Fn(&str) -> bool>(dir: &Path, matches: F) -> bool {
+    let dir = match dir.as_os_str().is_empty() {
+        true => Path::new("."),
+        _ => dir,
+    };
+    return std::fs::read_dir(dir).is_ok_and(|mut entries| {
+        entries.any(|entry| {
+            entry.is_ok_and(|entry| {
+                entry.path().is_file() && entry.file_name().to_str().is_some_and(&matches)
+            })
+        })
+    });
+}
+
+fn has_clean_target(makefile: &Path) -> bool {
+    let Ok(content) = std::fs::read_to_string(makefile) else {
+        return false;
+    };
+    return content.lines().any(|line| match line.split_once(':') {
+        Some((targets, recipe)) if !line.starts_with(char::is_whitespace) => {
+            !recipe.starts_with('=') && targets.split_whitespace().any(|t| t == "clean")
+        }
+        _ => false,
+    });
+}
 
 #[cfg(test)]
 mod tests {
-    use super::test::RmDirGuard;
+    use crate::test::{temp_dir, RmDirGuard};
     use std::{fs::create_dir_all, time::SystemTime};
 
     use crate::{
+        cache::Layout,
         conf::{Config, Plan},
         Result,
     };
@@ -220,6 +974,130 @@ mod tests {
         assert_eq!(mvn.args, ["-B", "clean"]);
     }
 
+    #[tokio::test]
+    async fn parse_composite_actions() {
+        let config = Config::load(
+            b"build.gradle = ./gradlew clean && .gradle/ ; buildSrc/build/\n\
+              pom.xml = mvn clean\n\
+              pom.xml = mvn -f sub clean"
+                .as_ref(),
+        )
+        .await
+        .unwrap();
+        let Some(Plan::All(plans)) = config.parse("build.gradle") else {
+            panic!("should be composite plan");
+        };
+        let plans = plans
+            .iter()
+            .map(|(plan, chained)| {
+                let dirs = plan.removes().into_iter().cloned().collect::<Vec<_>>();
+                (plan.cmd().to_string(), dirs, *chained)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            plans,
+            [
+                ("./gradlew".to_string(), vec![], false),
+                ("rm".to_string(), vec![".gradle".into()], true),
+                ("rm".to_string(), vec!["buildSrc/build".into()], false),
+            ]
+        );
+        let gradle = config.parse("build.gradle").unwrap();
+        assert_eq!(gradle.removes(), [".gradle", "buildSrc/build"]);
+
+        let Some(Plan::All(plans)) = config.parse("pom.xml") else {
+            panic!("repeated rules should be appended");
+        };
+        assert_eq!(plans.len(), 2);
+        assert!(plans.iter().all(|(_, chained)| !chained));
+
+        // the separators are never the arguments, even without spaces
+        let config = Config::load(b"a = make clean; true".as_ref())
+            .await
+            .unwrap();
+        let Some(Plan::All(plans)) = config.parse("a") else {
+            panic!("should be composite plan");
+        };
+        let cmds = plans.into_iter().map(|(plan, _)| plan.into_cmd().unwrap());
+        let cmds = cmds.map(|cmd| (cmd.command, cmd.args)).collect::<Vec<_>>();
+        assert_eq!(
+            cmds,
+            [
+                ("make".into(), vec!["clean".into()]),
+                ("true".into(), vec![])
+            ]
+        );
+
+        for invalid in ["a = b &&", "a = && b", "a = b ; ../c/"] {
+            assert!(Config::load(invalid.as_bytes()).await.is_err(), "{invalid}");
+        }
+    }
+
+    #[tokio::test]
+    async fn parse_conditional_rules() {
+        let config = Config::load(
+            b"node_modules/ if package.json\nbuild/ if build.gradle unless CMakeLists.txt".as_ref(),
+        )
+        .await
+        .unwrap();
+        let test = temp_dir("test-conditions");
+        let _guard = RmDirGuard(&test);
+        for (dir, files) in [
+            ("a/node_modules", ["a/package.json", ""]),
+            ("b/node_modules", ["", ""]),
+            ("c/build", ["c/build.gradle", ""]),
+            ("d/build", ["d/build.gradle", "d/pom.xml"]),
+            ("e/build", ["e/build.gradle", "e/CMakeLists.txt"]),
+            ("f/build", ["", ""]),
+        ] {
+            create_dir_all(test.join(dir)).unwrap();
+            for file in files.iter().filter(|file| !file.is_empty()) {
+                std::fs::write(test.join(file), "").unwrap();
+            }
+        }
+        let removes = |dir: &str| {
+            config
+                .parse(test.join(dir))
+                .and_then(|plan| plan.removes().into_iter().next().cloned())
+        };
+
+        assert_eq!(removes("a/node_modules"), Some("node_modules".into()));
+        assert_eq!(removes("b/node_modules"), None);
+        assert_eq!(removes("c/build"), Some("build".into()));
+        assert_eq!(removes("d/build"), Some("build".into()));
+        assert_eq!(removes("f/build"), None);
+        assert_eq!(
+            removes("e/build"),
+            Some("build".into()),
+            "falls back to the built-in cmake rule"
+        );
+
+        assert!(Config::load(b"node_modules/ if [".as_ref()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn parse_conditions_after_rule_name_only() {
+        let config = Config::load(
+            b"pom.xml unless mvnw = mvn clean if ready
+target/ if Cargo.toml => /tmp/a"
+                .as_ref(),
+        )
+        .await
+        .unwrap();
+
+        let mvn = &config.registry["pom.xml"][0];
+        assert_eq!(mvn.conditions.len(), 1);
+        assert_eq!(mvn.conditions[0].0.as_str(), "mvnw");
+        let mvn = mvn.plan.clone().into_cmd().unwrap();
+        assert_eq!(mvn.args, ["clean", "if", "ready"], "arguments");
+
+        let archive = &config.registry["target"][0];
+        assert_eq!(archive.conditions[0].0.as_str(), "Cargo.toml");
+        assert!(
+            matches!(&archive.plan, Plan::Archive(_, dir) if dir == std::path::Path::new("/tmp/a"))
+        );
+    }
+
     #[tokio::test]
     async fn fail_with_custom_empty_cmd() {
         let result = Config::load(b"pom.xml = ".as_ref()).await;
@@ -234,6 +1112,318 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn parse_protected_paths() {
+        let config = Config::load(b"!/tmp/keep\n! ~/release".as_ref())
+            .await
+            .unwrap();
+        assert!(config.protects("/tmp/keep"));
+        assert!(config.protects("/tmp/keep/node_modules"));
+        assert!(config.protects("/tmp/keep/a/../b"));
+        assert!(!config.protects("/tmp/keeper"));
+        assert!(!config.protects("/tmp/keep/.."));
+        assert!(config.protects(home::home_dir().unwrap().join("release/target")));
+    }
+
+    #[tokio::test]
+    async fn parse_archive_config() {
+        let config = Config::load(b"target/ => ~/archives".as_ref())
+            .await
+            .unwrap();
+        match config.registry["target"][0].plan.clone() {
+            Plan::Archive(dir, archives) => {
+                assert_eq!(dir, "target");
+                assert_eq!(archives, home::home_dir().unwrap().join("archives"));
+            }
+            _ => panic!("should be archive plan"),
+        }
+
+        assert!(Config::load(b"target/ => archives".as_ref()).await.is_err());
+        assert!(Config::load(b"../target/ => /tmp".as_ref()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn parse_file_glob_config() {
+        let config = Config::load(b"*.log\ncore.*".as_ref()).await.unwrap();
+        let test = temp_dir("test-files");
+        let _guard = RmDirGuard(&test);
+        create_dir_all(test.join("dir.log")).unwrap();
+        std::fs::write(test.join("a.log"), "").unwrap();
+
+        match config.parse(test.join("a.log")) {
+            Some(Plan::RmFiles(pattern)) => assert_eq!(pattern.as_str(), "*.log"),
+            _ => panic!("should be rm files plan"),
+        }
+        assert!(config.parse(test.join("dir.log")).is_none());
+        assert!(config.parse(test.join("a.txt")).is_none());
+
+        let config = Config::load(b"*.toml".as_ref()).await.unwrap();
+        std::fs::write(test.join("Cargo.toml"), "").unwrap();
+        std::fs::write(test.join("a.toml"), "").unwrap();
+        let cargo = config.parse(test.join("Cargo.toml")).unwrap();
+        assert_eq!(cargo.cmd(), "cargo", "built-in marker rather than glob");
+        assert!(matches!(
+            config.parse(test.join("a.toml")),
+            Some(Plan::RmFiles(_))
+        ));
+
+        assert!(Config::load(b"logs/*.log".as_ref()).await.is_err());
+        assert!(Config::load(b"*.sln = dotnet clean".as_ref())
+            .await
+            .is_err());
+        assert!(Config::load(b"[.log".as_ref()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn parse_toml_config() {
+        let config = Config::load_toml(
+            br#"
+protected = ["/tmp/keep"]
+disabled = ["go"]
+
+[[rules]]
+name = "pom.xml"
+run = "mvn -B clean && .mvn/"
+timeout = 300
+env = { MAVEN_OPTS = "-Xmx1g" }
+unless = ["build.gradle"]
+ecosystem = "java"
+
+[[rules]]
+name = "pom.xml"
+run = "mvn -B clean -Pnative"
+priority = 1
+
+[[rules]]
+name = "target/"
+archive = "/tmp/archives"
+
+[[rules]]
+name = "*.log"
+if = ["*.txt"]
+"#
+            .as_ref(),
+        )
+        .await
+        .unwrap();
+
+        assert!(config.protects("/tmp/keep/target"));
+        assert_eq!(config.disabled, ["go"]);
+        let rules = &config.registry["pom.xml"];
+        assert_eq!(rules[0].plan.cmd(), "mvn");
+        assert!(rules[0].plan.clone().into_cmd().unwrap().timeout.is_none());
+        match rules[1].plan.clone() {
+            Plan::All(plans) => {
+                let mvn = plans[0].0.clone().into_cmd().unwrap();
+                assert_eq!(mvn.args, ["-B", "clean"]);
+                assert_eq!(mvn.env, [("MAVEN_OPTS".into(), "-Xmx1g".into())]);
+                assert_eq!(mvn.timeout, Some(std::time::Duration::from_secs(300)));
+                assert!(matches!(&plans[1], (Plan::RmDir(dir), true) if dir == ".mvn"));
+            }
+            _ => panic!("should be composite plan"),
+        }
+        assert_eq!(rules[1].ecosystem.as_deref(), Some("java"));
+        assert_eq!(rules[1].conditions[0].0.as_str(), "build.gradle");
+        assert!(!rules[1].conditions[0].1);
+        assert!(matches!(
+            config.registry["target"][0].plan,
+            Plan::Archive(ref dir, _) if dir == "target"
+        ));
+        assert_eq!(config.files[0].0.as_str(), "*.log");
+        assert!(config.files[0].1.conditions[0].1);
+    }
+
+    #[tokio::test]
+    async fn parse_toml_rules_of_ecosystem() {
+        let mut config = Config::load_toml(
+            b"[[rules]]\nname = \"pom.xml\"\nrun = \"mvn clean\"\necosystem = \"java\"".as_ref(),
+        )
+        .await
+        .unwrap();
+
+        config.options.only = vec!["java".into()];
+        assert!(config.parse("pom.xml").is_some());
+        assert!(config.parse("Cargo.toml").is_none());
+
+        config.options.only = vec!["maven".into()];
+        assert!(config.parse("pom.xml").is_some());
+
+        let mut config = Config::load_toml(
+            b"[[rules]]\nname = \"pom.xml\"\nrun = \"mvn clean\"\necosystem = \"java\"\n\
+              [[rules]]\nname = \"pom.xml\"\nrun = \"rm-logs\""
+                .as_ref(),
+        )
+        .await
+        .unwrap();
+        config.options.skip = vec!["java".into()];
+        let plan = config.parse("pom.xml").unwrap();
+        assert_eq!(plan.cmd(), "rm-logs", "the untagged rule only");
+
+        config.options.skip = vec![];
+        config.options.only = vec!["java".into()];
+        let plan = config.parse("pom.xml").unwrap();
+        assert_eq!(plan.cmd(), "mvn", "the tagged rule only");
+        assert!(plan.into_cmd().is_some());
+    }
+
+    #[tokio::test]
+    async fn fail_with_invalid_toml_config() {
+        for config in [
+            "unknown = 1",
+            "protected = [\"release\"]",
+            "disabled = [\"node_modules\"]",
+            "[[rules]]\nname = \"pom.xml\"",
+            "[[rules]]\nname = \"target/\"\nrun = \"make clean\"",
+            "[[rules]]\nname = \"target/\"\narchive = \"archives\"",
+            "[[rules]]\nname = \"logs/*.log\"",
+            "[[rules]]\nname = \"../pom.xml\"\nrun = \"mvn clean\"",
+            "[[rules]]\nname = \"pom.xml\"\nrun = \"mvn clean &&\"",
+            "[[rules]]\nname = \"pom.xml\"\nrun = \"mvn clean\"\nretries = 1",
+            "[[rules]]\nname = \"target/\"\ntimeout = 60",
+            "[[rules]]\nname = \"target/\"\narchive = \"/tmp\"\nenv = { A = \"1\" }",
+            "[[rules]]\nname = \"*.log\"\ntimeout = 60",
+            "[[rules]]\nname = \"*.sln\"\nrun = \"dotnet clean\"",
+        ] {
+            assert!(
+                Config::load_toml(config.as_bytes()).await.is_err(),
+                "{config}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn fail_with_relative_protected_path() {
+        let result = Config::load(b"!release".as_ref()).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn fail_with_rule_names_escape_work_dir() {
+        for config in ["../target/", "/tmp/", "a/../../b/", "../pom.xml = rm -rf /"] {
+            let err = Config::load(config.as_bytes()).await.err().unwrap();
+            assert!(
+                err.to_string().starts_with("invalid rule name:"),
+                "{config}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn remove_symlink_rather_than_target() {
+        let test = temp_dir("test-rm-symlink");
+        let _guard = RmDirGuard(&test);
+        create_dir_all(test.join("project")).unwrap();
+        create_dir_all(test.join("shared/node_modules")).unwrap();
+        std::os::unix::fs::symlink(
+            test.join("shared/node_modules"),
+            test.join("project/node_modules"),
+        )
+        .unwrap();
+
+        let rm = Plan::RmDir("node_modules".into());
+        assert!(rm.run(test.join("project"), None).await.unwrap());
+        assert!(!test.join("project/node_modules").exists());
+        assert!(test.join("shared/node_modules").exists());
+
+        // the symlink is moved into the trash, or archived, as other dirs
+        let link = test.join("project/node_modules");
+        std::os::unix::fs::symlink(test.join("shared/node_modules"), &link).unwrap();
+        let trash = crate::trash::Trash::Freedesktop(test.join("Trash"));
+        assert!(rm.run(test.join("project"), Some(&trash)).await.unwrap());
+        assert!(link.symlink_metadata().is_err());
+        assert!(test.join("Trash/files/node_modules").is_symlink());
+        assert!(test.join("shared/node_modules").exists());
+
+        std::os::unix::fs::symlink(test.join("shared/node_modules"), &link).unwrap();
+        let archive = Plan::Archive("node_modules".into(), test.join("archives"));
+        assert!(archive.run(test.join("project"), None).await.unwrap());
+        assert!(link.symlink_metadata().is_err());
+        assert!(test.join("shared/node_modules").exists());
+        let archive = std::fs::read_dir(test.join("archives")).unwrap();
+        let archive = archive.flatten().next().unwrap().path();
+        let gz = flate2::read::GzDecoder::new(std::fs::File::open(archive).unwrap());
+        let mut tar = tar::Archive::new(gz);
+        let mut entries = tar.entries().unwrap();
+        let entry = entries.next().unwrap().unwrap();
+        assert!(entry.header().entry_type().is_symlink());
+        assert!(entries.next().is_none());
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn refuse_to_rm_dir_escapes_work_dir() {
+        let test = temp_dir("test-rm-escape");
+        let _guard = RmDirGuard(&test);
+        create_dir_all(test.join("project")).unwrap();
+        create_dir_all(test.join("shared/build")).unwrap();
+        std::os::unix::fs::symlink(test.join("shared"), test.join("project/buildSrc")).unwrap();
+
+        let rm = Plan::RmDir("buildSrc/build".into());
+        assert!(rm.run(test.join("project"), None).await.is_err());
+        assert!(test.join("shared/build").exists());
+    }
+
+    #[tokio::test]
+    async fn parse_selected_plans_only() {
+        let mut config = Config::load(b"node_modules/\npom.xml = mvn -B clean".as_ref())
+            .await
+            .unwrap();
+        let parsed = |config: &Config| {
+            ["Cargo.toml", "pom.xml", "go.mod", "tests/data/node_modules"]
+                .iter()
+                .filter(|path| config.parse(path).is_some())
+                .map(|path| path.rsplit('/').next().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        config.options.only = vec!["cargo".into(), "node".into()];
+        assert_eq!(parsed(&config), ["Cargo.toml", "node_modules"]);
+
+        config.options.only = vec!["maven".into(), "go.mod".into(), "rm".into()];
+        assert_eq!(parsed(&config), ["pom.xml", "go.mod", "node_modules"]);
+
+        config.options.only = vec![];
+        config.options.skip = vec!["mvn".into(), "go".into()];
+        assert_eq!(parsed(&config), ["Cargo.toml", "node_modules"]);
+    }
+
+    #[tokio::test]
+    async fn parse_disabled_builtins() {
+        let paths = ["Cargo.toml", "pom.xml", "go.mod"];
+        let parsed = |config: Config| {
+            paths
+                .iter()
+                .filter(|path| config.parse(path).is_some())
+                .copied()
+                .collect::<Vec<_>>()
+        };
+
+        let config = Config::load(b"disable go\ndisable pom.xml".as_ref())
+            .await
+            .unwrap();
+        assert_eq!(parsed(config), ["Cargo.toml"]);
+
+        let config = Config::load(b"disable go maven".as_ref()).await.unwrap();
+        assert_eq!(parsed(config), ["Cargo.toml"]);
+
+        let config = Config::load(b"disable *\npom.xml = mvn -B clean".as_ref())
+            .await
+            .unwrap();
+        assert_eq!(parsed(config), ["pom.xml"]);
+
+        let config = Config::load(b"disable = make disable".as_ref())
+            .await
+            .unwrap();
+        assert!(config.parse("disable").is_some(), "rule of the file");
+
+        assert!(Config::load(b"disable node_modules".as_ref())
+            .await
+            .is_err());
+        assert!(Config::load(b"!go".as_ref()).await.is_err(), "not a path");
+    }
+
     #[test]
     fn rm_dir_plan_apply_dir_only() {
         assert!(Plan::RmDir("target".into()).filter("target").is_some());
@@ -254,7 +1444,7 @@ mod tests {
         let _guard = RmDirGuard(&test);
 
         let rm = Plan::RmDir(test.file_name().unwrap().to_owned());
-        let result: Result<bool> = rm.run(tmp).await;
+        let result: Result<bool> = rm.run(tmp, None).await;
         assert!(result.unwrap());
         assert!(!test.exists(), "dir should be removed");
     }
@@ -262,14 +1452,14 @@ mod tests {
     #[tokio::test]
     async fn return_immediately_when_rm_dir_which_did_not_exists() {
         let rm = Plan::RmDir("node_modules".into());
-        let result: Result<bool> = rm.run(".").await;
+        let result: Result<bool> = rm.run(".", None).await;
         assert!(result.unwrap());
     }
 
     #[tokio::test]
     async fn return_immediately_work_dir_did_not_exists() {
         let rm = Plan::RmDir("node_modules".into());
-        let result: Result<bool> = rm.run("/home/unknown").await;
+        let result: Result<bool> = rm.run("/home/unknown", None).await;
         assert!(result.unwrap());
     }
 
@@ -294,14 +1484,200 @@ mod tests {
                 test.file_name().unwrap().to_string_lossy().to_string(),
             ],
         ));
-        let result: Result<bool> = rm.run(tmp).await;
+        let result: Result<bool> = rm.run(tmp, None).await;
         assert!(result.unwrap());
         assert!(!test.exists(), "dir should be removed");
     }
 
+    #[tokio::test]
+    async fn run_all_plan_in_order() {
+        let test = temp_dir("test-all");
+        let _guard = RmDirGuard(&test);
+        for dir in ["a", "b"] {
+            create_dir_all(test.join(dir)).unwrap();
+        }
+        let fail = Plan::Cmd(crate::cmd::Cmd::new("false", Vec::<String>::new()));
+
+        let all = fail
+            .then(Plan::RmDir("a".into()), true)
+            .then(Plan::RmDir("b".into()), false);
+        assert!(!all.run(&test, None).await.unwrap());
+        assert!(test.join("a").exists(), "skipped after failure");
+        assert!(!test.join("b").exists(), "continued after failure");
+
+        let all = Plan::RmDir("b".into()).then(Plan::RmDir("a".into()), true);
+        assert!(all.run(&test, None).await.unwrap());
+        assert!(!test.join("a").exists());
+
+        let absent = Plan::Cmd(crate::cmd::Cmd::new("absent-command", Vec::<String>::new()));
+        for dir in ["a", "b"] {
+            create_dir_all(test.join(dir)).unwrap();
+        }
+        let all = absent
+            .clone()
+            .then(Plan::RmDir("a".into()), true)
+            .then(Plan::RmDir("b".into()), false);
+        assert!(all.run(&test, None).await.is_err(), "errors are propagated");
+        assert!(test.join("a").exists(), "skipped after error");
+        assert!(!test.join("b").exists(), "continued after error");
+
+        let all = Plan::RmDir("a".into()).then(absent, false);
+        assert!(all.run(&test, None).await.is_err(), "errors are propagated");
+        assert!(!test.join("a").exists());
+    }
+
+    #[test]
+    fn parse_builtin_build_dirs_next_to_marker_only() {
+        let test = temp_dir("test-build-dirs");
+        let _guard = RmDirGuard(&test);
+        let dirs = [
+            "cmake/build",
+            "cmake/cmake-build-debug",
+            "meson/builddir",
+            "dotnet/bin",
+            "dotnet/obj",
+        ];
+        for dir in dirs.iter().chain(&["other/build", "other/bin"]) {
+            create_dir_all(test.join(dir)).unwrap();
+        }
+        std::fs::write(test.join("cmake/CMakeLists.txt"), "").unwrap();
+        std::fs::write(test.join("meson/meson.build"), "").unwrap();
+        std::fs::write(test.join("dotnet/App.csproj"), "").unwrap();
+
+        let config = Config::empty();
+        for dir in dirs {
+            assert!(matches!(
+                config.parse(test.join(dir)).unwrap(),
+                Plan::RmDir(name) if test.join(dir).ends_with(&name)
+            ));
+        }
+        assert!(config.parse(test.join("other/build")).is_none());
+        assert!(config.parse(test.join("other/bin")).is_none());
+    }
+
+    #[test]
+    fn parse_makefile_with_clean_target_only() {
+        let test = temp_dir("test-makefiles");
+        let _guard = RmDirGuard(&test);
+        for dir in ["clean", "phony", "assign", "absent"] {
+            create_dir_all(test.join(dir)).unwrap();
+        }
+        std::fs::write(
+            test.join("clean/Makefile"),
+            "all:\n\tcc\nclean:\n\trm -f a.out",
+        )
+        .unwrap();
+        std::fs::write(
+            test.join("phony/Makefile"),
+            ".PHONY: clean\nclean distclean: \n",
+        )
+        .unwrap();
+        std::fs::write(test.join("assign/Makefile"), "clean := rm\n\tclean: a\n").unwrap();
+        std::fs::write(test.join("absent/Makefile"), "all:\n\tcc").unwrap();
+
+        let config = Config::empty();
+        for dir in ["clean", "phony"] {
+            let make = config.parse(test.join(dir).join("Makefile")).unwrap();
+            assert_eq!(make.into_cmd().unwrap().command, "make");
+        }
+        for dir in ["assign", "absent"] {
+            assert!(config.parse(test.join(dir).join("Makefile")).is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn run_archive_plan() {
+        let test = temp_dir("test-archive");
+        let _guard = RmDirGuard(&test);
+        create_dir_all(test.join("project/target/debug")).unwrap();
+        std::fs::write(test.join("project/target/debug/a"), "a").unwrap();
+
+        let archive = Plan::Archive("target".into(), test.join("archives"));
+        assert!(archive.run(test.join("project"), None).await.unwrap());
+        assert!(!test.join("project/target").exists());
+
+        let mut archives = std::fs::read_dir(test.join("archives")).unwrap();
+        let archive = archives.next().unwrap().unwrap();
+        let name = archive.file_name().into_string().unwrap();
+        assert!(name.starts_with("project-target-") && name.ends_with(".tar.gz"));
+        let gz = flate2::read::GzDecoder::new(std::fs::File::open(archive.path()).unwrap());
+        let mut tar = tar::Archive::new(gz);
+        let entries = tar
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().into_owned())
+            .collect::<Vec<_>>();
+        assert!(entries.contains(&"target/debug/a".into()));
+
+        create_dir_all(test.join("other/project/target")).unwrap();
+        let archive = Plan::Archive("target".into(), test.join("archives"));
+        assert!(archive.run(test.join("other/project"), None).await.unwrap());
+        let archives = std::fs::read_dir(test.join("archives")).unwrap();
+        assert_eq!(archives.count(), 2, "same project name, same second");
+    }
+
+    #[tokio::test]
+    async fn run_rm_files_plan() {
+        let test = temp_dir("test-rm-files");
+        let _guard = RmDirGuard(&test);
+        create_dir_all(test.join("dir.log")).unwrap();
+        for file in ["a.log", "b.log", "c.txt", "CMakeLists.txt"] {
+            std::fs::write(test.join(file), "").unwrap();
+        }
+
+        let rm = Plan::RmFiles(glob::Pattern::new("*.log").unwrap());
+        assert!(rm.run(&test, None).await.unwrap());
+        assert!(!test.join("a.log").exists());
+        assert!(!test.join("b.log").exists());
+        assert!(test.join("c.txt").exists());
+
+        let rm = Plan::RmFiles(glob::Pattern::new("*.txt").unwrap());
+        assert!(rm.run(&test, None).await.unwrap());
+        assert!(!test.join("c.txt").exists());
+        assert!(
+            test.join("CMakeLists.txt").exists(),
+            "built-in markers are never removed"
+        );
+        assert!(
+            test.join("dir.log").is_dir(),
+            "directories are never removed"
+        );
+    }
+
+    #[tokio::test]
+    async fn run_prune_plan() {
+        let test = temp_dir("test-prune");
+        let _guard = RmDirGuard(&test);
+        create_dir_all(test.join("src/registry/stale/src")).unwrap();
+        create_dir_all(test.join("src/registry/used/src")).unwrap();
+        std::fs::write(test.join("src/registry/stale/src/lib.rs"), "").unwrap();
+        std::fs::write(test.join("src/registry/used/.cargo-ok"), "").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        std::fs::write(test.join("src/registry/used/src/lib.rs"), "").unwrap();
+        let before = test
+            .join("src/registry/used/src/lib.rs")
+            .metadata()
+            .unwrap()
+            .modified()
+            .unwrap();
+
+        let prune = |before| Plan::Prune(before, Layout::Cargo);
+        assert!(prune(Some(before)).run(&test, None).await.unwrap());
+        assert!(!test.join("src/registry/stale").exists());
+        assert!(
+            test.join("src/registry/used/.cargo-ok").exists(),
+            "entries are pruned as a whole by the newest modified time"
+        );
+
+        assert!(prune(None).run(&test, None).await.unwrap());
+        assert!(test.exists());
+        assert!(!test.join("src").exists());
+    }
+
     #[test]
     fn plan_cmd() {
         assert_eq!(Plan::Cmd("pom.xml".parse().unwrap()).cmd(), "mvn");
         assert_eq!(Plan::RmDir("node_modules".into()).cmd(), "rm");
+        assert_eq!(Plan::Prune(None, Layout::Files).cmd(), "prune");
     }
 }
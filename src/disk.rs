@@ -0,0 +1,292 @@
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
+};
+
+use crate::Error;
+
+/// Disk usage of `path` in bytes, symlinks are counted but never followed.
+pub(crate) fn size<P: AsRef<Path>>(path: P) -> u64 {
+    let path = path.as_ref();
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::read_dir(path).map_or(0, |entries| {
+            entries.flatten().map(|entry| size(entry.path())).sum()
+        }),
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    }
+}
+
+/// Removes the entries of `dir` not modified since `before`, judged by the
+/// newest modified time inside each of them. The files and the directories
+/// accepted by `entry` are removed as a whole, the others are pruned in turn,
+/// and removed once left empty. All of the content is removed if `before` is absent.
+pub(crate) fn prune<P, F>(dir: P, before: Option<SystemTime>, entry: &F) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    F: Fn(&Path) -> bool,
+{
+    for child in fs::read_dir(dir)? {
+        let path = child?.path();
+        let meta = fs::symlink_metadata(&path)?;
+        if meta.is_dir() && !entry(&path) {
+            prune(&path, before, entry)?;
+            if fs::read_dir(&path)?.next().is_none() {
+                fs::remove_dir(&path)?;
+            }
+            continue;
+        }
+        let expired = before.map_or(true, |before| {
+            newest(&path, usize::MAX).is_some_and(|newest| newest < before)
+        });
+        match meta.is_dir() {
+            true if expired => remove_dir_all::remove_dir_all(&path)?,
+            false if expired => fs::remove_file(&path)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Copies the `src` to the `dest` recursively with the permissions, symlinks
+/// are copied as is rather than followed.
+pub(crate) fn copy<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dest: Q) -> io::Result<()> {
+    let (src, dest) = (src.as_ref(), dest.as_ref());
+    let meta = fs::symlink_metadata(src)?;
+    if meta.is_symlink() {
+        let target = fs::read_link(src)?;
+        #[cfg(unix)]
+        return std::os::unix::fs::symlink(target, dest);
+        #[cfg(windows)]
+        return match fs::metadata(src).is_ok_and(|meta| meta.is_dir()) {
+            true => std::os::windows::fs::symlink_dir(target, dest),
+            false => std::os::windows::fs::symlink_file(target, dest),
+        };
+    }
+    if !meta.is_dir() {
+        return fs::copy(src, dest).map(|_| ());
+    }
+    fs::create_dir(dest)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        copy(entry.path(), dest.join(entry.file_name()))?;
+    }
+    // the read-only directories are copied into before
+    return fs::set_permissions(dest, meta.permissions());
+}
+
+/// Packs the `dir` into the gzipped tar `archive` as `name`, symlinks are kept
+/// as is. The archive is written aside and renamed once complete, it fails
+/// rather than overwrites an existing archive.
+pub(crate) fn archive<P: AsRef<Path>>(dir: P, name: &Path, archive: &Path) -> io::Result<()> {
+    use flate2::{write::GzEncoder, Compression};
+    if let Some(parent) = archive.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let create_new = |path: &Path| fs::File::options().write(true).create_new(true).open(path);
+    // the archive is claimed first, which the complete one replaces
+    create_new(archive)?;
+    let mut partial = archive.as_os_str().to_owned();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+    let file = create_new(&partial).map_err(|err| {
+        let _ = fs::remove_file(archive);
+        err
+    })?;
+    let pack = || {
+        let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        tar.follow_symlinks(false);
+        // a symlink is archived itself rather than the target
+        match dir.as_ref() {
+            link if link.is_symlink() => tar.append_path_with_name(link, name)?,
+            dir => tar.append_dir_all(name, dir)?,
+        }
+        tar.into_inner()?.finish()?;
+        fs::rename(&partial, archive)
+    };
+    return pack().map_err(|err| {
+        let _ = fs::remove_file(&partial);
+        let _ = fs::remove_file(archive);
+        err
+    });
+}
+
+/// Latest modified time of `path` and the content at most `depth` levels below
+/// it, symlinks are never followed.
+pub(crate) fn newest<P: AsRef<Path>>(path: P, depth: usize) -> Option<SystemTime> {
+    let path = path.as_ref();
+    let meta = fs::symlink_metadata(path).ok()?;
+    let modified = meta.modified().ok()?;
+    match fs::read_dir(path) {
+        Ok(entries) if meta.is_dir() && depth > 0 => entries
+            .flatten()
+            .filter_map(|entry| newest(entry.path(), depth - 1))
+            .chain([modified])
+            .max(),
+        _ => Some(modified),
+    }
+}
+
+/// Whether the `file` is locked by another process, e.g: cargo holds
+/// `target/debug/.cargo-lock` while building.
+pub(crate) fn locked<P: AsRef<Path>>(file: P) -> bool {
+    use fs2::FileExt;
+    let Ok(file) = fs::File::open(file) else {
+        return false;
+    };
+    // the lock is released once the file is closed
+    match file.try_lock_exclusive() {
+        Ok(()) => false,
+        Err(err) => err.raw_os_error() == fs2::lock_contended_error().raw_os_error(),
+    }
+}
+
+/// Space available to the user on the filesystem holding `path`.
+pub(crate) fn available<P: AsRef<Path>>(path: P) -> io::Result<u64> {
+    fs2::available_space(path)
+}
+
+/// Human readable bytes, e.g: `1.5 GiB`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Bytes(pub u64);
+
+impl Display for Bytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+        let (mut size, mut unit) = (self.0 as f64 / 1024.0, 0);
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+        return write!(f, "{size:.1} {}", UNITS[unit]);
+    }
+}
+
+impl FromStr for Bytes {
+    type Err = Error;
+
+    /// Parses sizes in binary units, e.g: `512`, `100K`, `1.5G`, `2GiB`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let unit = s.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        let size = s[..s.len() - unit.len()]
+            .parse::<f64>()
+            .map_err(|_| Error::other(format!("invalid size: `{s}`")))?;
+        let shift = match unit.trim().to_ascii_uppercase().as_str() {
+            "" | "B" => 0,
+            "K" | "KB" | "KIB" => 10,
+            "M" | "MB" | "MIB" => 20,
+            "G" | "GB" | "GIB" => 30,
+            "T" | "TB" | "TIB" => 40,
+            _ => return Err(Error::other(format!("invalid size: `{s}`"))),
+        };
+        return Ok(Bytes((size * (1u64 << shift) as f64) as u64));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        time::{Duration, SystemTime},
+    };
+
+    use super::{archive, locked, newest, size, Bytes};
+    use crate::test::{temp_dir, RmDirGuard};
+
+    #[test]
+    fn size_of_dir_recursively() {
+        let test = std::env::temp_dir().join(format!(
+            "test-size-{}",
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let _guard = RmDirGuard(&test);
+        fs::create_dir_all(test.join("a/b")).unwrap();
+        fs::write(test.join("a/one"), [0; 10]).unwrap();
+        fs::write(test.join("a/b/two"), [0; 20]).unwrap();
+
+        assert_eq!(size(&test), 30);
+        assert_eq!(size(test.join("a/one")), 10);
+        assert_eq!(size(test.join("absent")), 0);
+    }
+
+    #[test]
+    fn newest_within_depth() {
+        let test = temp_dir("test-newest");
+        let _guard = RmDirGuard(&test);
+        fs::create_dir_all(test.join("a/b")).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        fs::write(test.join("a/b/c"), "").unwrap();
+        let c = fs::metadata(test.join("a/b/c"))
+            .unwrap()
+            .modified()
+            .unwrap();
+
+        // the parent dir of `c` may be stamped finer than `c` itself
+        assert!(newest(&test, 3).unwrap() >= c);
+        assert!(newest(&test, 1).unwrap() < c);
+        assert_eq!(newest(test.join("absent"), 3), None);
+    }
+
+    #[test]
+    fn archive_never_overwrites() {
+        let test = temp_dir("test-archive-exists");
+        let _guard = RmDirGuard(&test);
+        fs::create_dir_all(test.join("target")).unwrap();
+        fs::write(test.join("a.tar.gz"), "a").unwrap();
+
+        let name = std::path::Path::new("target");
+        let err = archive(test.join("target"), name, &test.join("a.tar.gz")).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(test.join("a.tar.gz")).unwrap(), "a");
+
+        archive(test.join("target"), name, &test.join("b.tar.gz")).unwrap();
+        assert!(fs::metadata(test.join("b.tar.gz")).unwrap().len() > 0);
+        assert!(!test.join("b.tar.gz.part").exists());
+    }
+
+    #[test]
+    fn locked_by_others() {
+        use fs2::FileExt;
+        let test = temp_dir("test-locked");
+        let _guard = RmDirGuard(&test);
+        fs::create_dir_all(&test).unwrap();
+        let lock = test.join(".cargo-lock");
+        let file = fs::File::create(&lock).unwrap();
+
+        assert!(!locked(&lock));
+        file.lock_exclusive().unwrap();
+        assert!(locked(&lock));
+        file.unlock().unwrap();
+        assert!(!locked(&lock));
+        assert!(!locked(test.join("absent")));
+    }
+
+    #[test]
+    fn human_readable_bytes() {
+        assert_eq!(Bytes(0).to_string(), "0 B");
+        assert_eq!(Bytes(1023).to_string(), "1023 B");
+        assert_eq!(Bytes(1536).to_string(), "1.5 KiB");
+        assert_eq!(Bytes(5 << 30).to_string(), "5.0 GiB");
+    }
+
+    #[test]
+    fn parse_bytes() {
+        assert_eq!("512".parse::<Bytes>().unwrap(), Bytes(512));
+        assert_eq!("100K".parse::<Bytes>().unwrap(), Bytes(100 << 10));
+        assert_eq!("1.5G".parse::<Bytes>().unwrap(), Bytes(3 << 29));
+        assert_eq!("2 GiB".parse::<Bytes>().unwrap(), Bytes(2 << 30));
+        assert_eq!("10mb".parse::<Bytes>().unwrap(), Bytes(10 << 20));
+        assert!("".parse::<Bytes>().is_err());
+        assert!("1X".parse::<Bytes>().is_err());
+    }
+}
@@ -4,6 +4,8 @@ use std::{fmt::Display, io};
 pub enum Error {
     IO(io::Error),
     Message(String),
+    /// The command to run is not found, e.g: the tool is not installed.
+    NotFound(String),
 }
 
 impl std::error::Error for Error {}
@@ -13,6 +15,7 @@ impl Display for Error {
         match self {
             Error::IO(err) => err.fmt(f),
             Error::Message(err) => err.fmt(f),
+            Error::NotFound(command) => write!(f, "`{command}` not found"),
         }
     }
 }
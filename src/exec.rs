@@ -0,0 +1,406 @@
+use std::{
+    io::Write,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use termcolor::{Buffer, BufferWriter, Color, ColorChoice, ColorSpec, HyperlinkSpec, WriteColor};
+
+use crate::{
+    conf::{Config, Plan},
+    disk,
+    disk::Bytes,
+    git,
+    trash::Trash,
+    Error, IOResult, Result,
+};
+
+/// Lock files relative to the work dir which the builds hold while running.
+const LOCKS: [&str; 3] = [
+    "target/.cargo-lock",
+    "target/*/.cargo-lock",
+    ".gradle/**/*.lock",
+];
+
+#[derive(Debug, Clone)]
+pub(crate) struct Execution<'a> {
+    pub plan: Plan<'a>,
+    pub work_dir: PathBuf,
+    /// Paths measured before and after the run to report the freed space.
+    pub measured: Vec<PathBuf>,
+    /// The latest modified time of the files under the work dir.
+    pub modified: SystemTime,
+    /// Reclaimable size of the build outputs, `None` if unknown or not measured.
+    pub size: Option<u64>,
+    /// Where the removed directories are moved to, deleted permanently if absent.
+    pub trash: Option<Trash>,
+}
+
+impl<'a> Execution<'a> {
+    pub fn new(plan: Plan<'a>, work_dir: PathBuf) -> Self {
+        Execution {
+            plan,
+            work_dir,
+            measured: vec![],
+            modified: SystemTime::UNIX_EPOCH,
+            size: None,
+            trash: None,
+        }
+    }
+
+    pub async fn run(&self) -> Result<bool> {
+        let before = self.measure(&self.measured).await?;
+        let result = self.plan.run(&self.work_dir, self.trash.as_ref()).await;
+        let freed = match before {
+            Some(before) => self
+                .measure(&self.measured)
+                .await?
+                .map(|after| before.saturating_sub(after)),
+            _ => None,
+        };
+        let freed = freed.map(|freed| format!("{} freed", Bytes(freed)));
+        return match result {
+            Ok(true) => self.report(Color::Green, "ok", freed).map(|_| true),
+            // the other executions run anyway, e.g: the tool is not installed
+            Err(err)
+                if matches!(self.plan, Plan::Cmd(_))
+                    && matches!(err.downcast_ref(), Some(Error::NotFound(_))) =>
+            {
+                self.skip(&err.to_string()).map(|_| false)
+            }
+            result => {
+                self.report(Color::Red, "error", freed)?;
+                result
+            }
+        };
+    }
+
+    /// The paths which the execution cleans, the work dir if it removes none.
+    pub fn targets(&self) -> Vec<PathBuf> {
+        match self.plan.removes() {
+            dirs if dirs.is_empty() => vec![self.work_dir.clone()],
+            dirs => dirs.iter().map(|dir| self.work_dir.join(dir)).collect(),
+        }
+    }
+
+    /// Returns the reason if the execution should be skipped by the `config`.
+    pub async fn skip_reason(&self, config: &Config) -> Option<String> {
+        if self.targets().iter().any(|target| config.protects(target)) {
+            return Some("protected".to_string());
+        }
+        let (options, elapsed) = (&config.options, self.modified.elapsed().unwrap_or_default());
+        match (options.older_than, options.min_size, self.size) {
+            (Some(age), ..) if elapsed < age => {
+                return Some(format!("modified {} ago", Ago(elapsed)))
+            }
+            (_, Some(min), Some(size)) if size < min.0 => {
+                return Some(format!("{} reclaimable only", Bytes(size)))
+            }
+            (_, Some(_), None) => return Some("reclaimable size unknown".to_string()),
+            _ => {}
+        }
+        if let Some(activity) = self.activity(options.active_within).await {
+            return Some(activity);
+        }
+        if options.skip_dirty && git::dirty(&self.work_dir).await {
+            return Some("uncommitted changes".to_string());
+        }
+
+        // never clean the content committed to git, and with `git_ignored`
+        // only the content ignored by git, like `git clean -X` does.
+        let Some(outputs) = self.plan.outputs(&self.work_dir) else {
+            return options
+                .git_ignored
+                .then(|| "build outputs unknown".to_string());
+        };
+        if outputs.is_empty() {
+            return None;
+        }
+        if let Some(output) = git::tracked(&self.work_dir, &outputs).await {
+            return Some(format!("{} tracked by git", output.display()));
+        }
+        if !options.git_ignored {
+            return None;
+        }
+        return git::unignored(&self.work_dir, &outputs)
+            .await
+            .map(|output| format!("{} not ignored by git", output.display()));
+    }
+
+    /// Returns the reason if the project looks being built: a build lock is
+    /// held, or the build outputs are written within `active_within`.
+    async fn activity(&self, active_within: Option<Duration>) -> Option<String> {
+        let (work_dir, outputs) = (self.work_dir.clone(), self.plan.outputs(&self.work_dir));
+        let activity = tokio::task::spawn_blocking(move || {
+            let root = PathBuf::from(glob::Pattern::escape(&work_dir.to_string_lossy()));
+            let locks = LOCKS
+                .iter()
+                .filter_map(|lock| glob::glob(&root.join(lock).to_string_lossy()).ok())
+                .flat_map(|paths| paths.flatten());
+            for lock in locks {
+                if disk::locked(&lock) {
+                    let lock = lock.strip_prefix(&work_dir).unwrap_or(&lock);
+                    return Some(format!("{} is locked by a running build", lock.display()));
+                }
+            }
+            let newest = outputs?
+                .iter()
+                .filter_map(|output| disk::newest(work_dir.join(output), 3))
+                .max()?;
+            let elapsed = newest.elapsed().unwrap_or_default();
+            return (elapsed < active_within?)
+                .then(|| format!("build outputs written {} ago", Ago(elapsed)));
+        });
+        return activity.await.ok().flatten();
+    }
+
+    /// Ranks the execution by the reclaimable size weighted by the days since
+    /// the last modification, so larger and staler projects rank higher.
+    pub fn rank(&self) -> f64 {
+        let days = self.modified.elapsed().unwrap_or_default().as_secs_f64() / 86400.0;
+        return self.size.unwrap_or(0) as f64 * (1.0 + days);
+    }
+
+    /// Size of the build outputs that the plan cleans, `None` if unknown.
+    pub async fn reclaimable(&self) -> Result<Option<u64>> {
+        match self.plan.outputs(&self.work_dir) {
+            Some(outputs) => {
+                let outputs = outputs.iter().map(|path| self.work_dir.join(path));
+                self.measure(&outputs.collect::<Vec<_>>()).await
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Reports the reclaimable size found before running.
+    pub fn found(&self) -> Result<()> {
+        let size = self
+            .size
+            .map_or("?".to_string(), |size| Bytes(size).to_string());
+        self.report(Color::Blue, "found", Some(size))
+    }
+
+    pub fn skip(&self, reason: &str) -> Result<()> {
+        self.report(Color::Yellow, "skipped", Some(reason.to_string()))
+    }
+
+    fn report(&self, fg: Color, status: &str, detail: Option<String>) -> Result<()> {
+        use std::io::{stdout, IsTerminal};
+        let out = BufferWriter::stdout(match stdout().is_terminal() {
+            true => ColorChoice::Always,
+            _ => ColorChoice::Never,
+        });
+        let mut buf = try_concat(tag(self, &out), colorized_text(status, fg, &out))?;
+        if let Some(detail) = detail {
+            write!(buf, " ({detail})")?;
+        }
+        buf.write_all(b"\n")?;
+        return Ok(out.print(&buf)?);
+
+        fn try_concat(head: IOResult<Buffer>, tail: IOResult<Buffer>) -> IOResult<Buffer> {
+            let mut buf = Buffer::ansi();
+            buf.write_all(head?.as_slice())?;
+            buf.write_all(tail?.as_slice())?;
+            return Ok(buf);
+        }
+
+        fn tag(exe: &Execution, out: &BufferWriter) -> IOResult<Buffer> {
+            let mut buf = colorized_text(exe.plan.cmd().as_ref(), Color::Cyan, out)?;
+            let url = {
+                use path_absolutize::Absolutize;
+                let url = format!("file://{}", exe.work_dir.absolutize()?.display());
+                #[cfg(target_os = "windows")]
+                let url = url.replace('\\', "/");
+                url
+            };
+            write!(buf, " clean: ")?;
+            buf.set_hyperlink(&HyperlinkSpec::open(url.as_bytes()))?;
+            write!(buf, "{}", exe.work_dir.display())?;
+            buf.set_hyperlink(&HyperlinkSpec::close())?;
+            write!(buf, "? ")?;
+            return Ok(buf);
+        }
+
+        fn colorized_text(text: &str, fg: Color, out: &BufferWriter) -> IOResult<Buffer> {
+            let mut buf = out.buffer();
+            let mut spec = ColorSpec::new();
+            buf.set_color(spec.set_fg(Some(fg)))?;
+            write!(buf, "{}", text)?;
+            spec.clear();
+            buf.set_color(&spec)?;
+            return Ok(buf);
+        }
+    }
+
+    /// Total size of the paths, `None` if nothing is measured.
+    async fn measure(&self, paths: &[PathBuf]) -> Result<Option<u64>> {
+        if paths.is_empty() {
+            return Ok(None);
+        }
+        let measured = paths.to_vec();
+        let size = tokio::task::spawn_blocking(move || measured.iter().map(disk::size).sum());
+        return Ok(Some(size.await?));
+    }
+}
+
+/// Human readable elapsed time in the largest unit, e.g: `3 days`.
+struct Ago(Duration);
+
+impl std::fmt::Display for Ago {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const UNITS: [(u64, &str); 4] = [
+            (86400, "day"),
+            (3600, "hour"),
+            (60, "minute"),
+            (1, "second"),
+        ];
+        let secs = self.0.as_secs();
+        let (size, unit) = UNITS
+            .iter()
+            .find(|(size, _)| secs >= *size)
+            .unwrap_or(&UNITS[3]);
+        let n = secs / size;
+        return write!(f, "{n} {unit}{}", if n == 1 { "" } else { "s" });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use super::{Ago, Execution};
+    use crate::{
+        conf::{Config, Plan},
+        disk::Bytes,
+        test::{temp_dir, RmDirGuard},
+    };
+
+    /// A project of an unlocked build lock outside of git, whose state never
+    /// depends on the builds of the repo, e.g: `./target/debug/.cargo-lock`.
+    fn project(prefix: &str) -> (PathBuf, RmDirGuard<PathBuf>) {
+        let project = temp_dir(prefix);
+        std::fs::create_dir_all(project.join("target/debug")).unwrap();
+        std::fs::write(project.join("target/debug/.cargo-lock"), "").unwrap();
+        return (project.clone(), RmDirGuard(project));
+    }
+
+    #[tokio::test]
+    async fn skip_recently_modified() {
+        let day = Duration::from_secs(86400);
+        let (project, _guard) = project("clean-modified");
+        let mut exe = Execution::new(Plan::RmDir("target".into()), project);
+        let mut config = Config::empty();
+        config.options.older_than = Some(day * 7);
+
+        exe.modified = SystemTime::now() - day * 2;
+        assert_eq!(
+            exe.skip_reason(&config).await.unwrap(),
+            "modified 2 days ago"
+        );
+        assert!(exe.skip_reason(&Config::empty()).await.is_none());
+
+        exe.modified = SystemTime::now() - day * 8;
+        assert!(exe.skip_reason(&config).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn skip_small_build_outputs() {
+        let (project, _guard) = project("clean-small");
+        let mut exe = Execution::new(Plan::RmDir("target".into()), project);
+        let mut config = Config::empty();
+        config.options.min_size = Some(Bytes(1024));
+
+        exe.size = Some(512);
+        assert_eq!(
+            exe.skip_reason(&config).await.unwrap(),
+            "512 B reclaimable only"
+        );
+
+        exe.size = Some(1024);
+        assert!(exe.skip_reason(&config).await.is_none());
+
+        exe.size = None;
+        assert_eq!(
+            exe.skip_reason(&config).await.unwrap(),
+            "reclaimable size unknown"
+        );
+        assert!(exe.skip_reason(&Config::empty()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn skip_active_builds() {
+        use fs2::FileExt;
+        let project = temp_dir("clean-active");
+        let _guard = RmDirGuard(&project);
+        std::fs::create_dir_all(project.join("target/debug")).unwrap();
+        let lock = std::fs::File::create(project.join("target/debug/.cargo-lock")).unwrap();
+        let exe = Execution::new(Plan::RmDir("target".into()), project.clone());
+        let mut config = Config::empty();
+
+        lock.lock_exclusive().unwrap();
+        let reason = exe.skip_reason(&config).await.unwrap();
+        assert!(reason.ends_with(".cargo-lock is locked by a running build"));
+        lock.unlock().unwrap();
+        assert!(exe.skip_reason(&config).await.is_none());
+
+        config.options.active_within = Some(Duration::from_secs(3600));
+        let reason = exe.skip_reason(&config).await.unwrap();
+        assert!(reason.starts_with("build outputs written"));
+    }
+
+    #[tokio::test]
+    async fn skip_protected() {
+        let config = Config::load(b"!/tmp/keep".as_ref()).await.unwrap();
+        let rm = |dir: &str| Execution::new(Plan::RmDir("target".into()), PathBuf::from(dir));
+
+        assert_eq!(
+            rm("/tmp/keep").skip_reason(&config).await.unwrap(),
+            "protected"
+        );
+        assert_eq!(
+            rm("/tmp/keep/a").skip_reason(&config).await.unwrap(),
+            "protected"
+        );
+        assert!(rm("/tmp").skip_reason(&config).await.is_none());
+
+        let config = Config::load(b"!/tmp/a/keep".as_ref()).await.unwrap();
+        let rm = Plan::RmDir("target".into()).then(Plan::RmDir("keep".into()), false);
+        let exe = Execution::new(rm, PathBuf::from("/tmp/a"));
+        assert_eq!(exe.skip_reason(&config).await.unwrap(), "protected");
+    }
+
+    #[tokio::test]
+    async fn skip_commands_not_found() {
+        let cmd = Plan::Cmd("!clean-rs-missing-tool clean".parse().unwrap());
+        let exe = Execution::new(cmd, std::env::temp_dir());
+
+        assert!(!exe.run().await.unwrap());
+    }
+
+    #[test]
+    fn rank_by_size_and_staleness() {
+        let day = Duration::from_secs(86400);
+        let (project, _guard) = project("clean-rank");
+        let exe = |size, days| Execution {
+            size,
+            modified: SystemTime::now() - day * days,
+            ..Execution::new(Plan::RmDir("target".into()), project.clone())
+        };
+
+        assert!(exe(Some(2048), 0).rank() > exe(Some(1024), 0).rank());
+        assert!(exe(Some(1024), 9).rank() > exe(Some(2048), 0).rank());
+        assert!(exe(Some(1), 0).rank() > exe(None, 9).rank());
+    }
+
+    #[test]
+    fn human_readable_elapsed_time() {
+        assert_eq!(Ago(Duration::from_secs(0)).to_string(), "0 seconds");
+        assert_eq!(Ago(Duration::from_secs(1)).to_string(), "1 second");
+        assert_eq!(Ago(Duration::from_secs(150)).to_string(), "2 minutes");
+        assert_eq!(Ago(Duration::from_secs(3600 * 5)).to_string(), "5 hours");
+        assert_eq!(Ago(Duration::from_secs(86400)).to_string(), "1 day");
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+use crate::cmd::Cmd;
+
+/// The first of the `paths` relative to the `work_dir` under which git tracks
+/// any file, by a single git call, `None` if the work dir is not inside a
+/// repository or git is unavailable.
+pub(crate) async fn tracked<P: AsRef<Path>>(work_dir: P, paths: &[PathBuf]) -> Option<&PathBuf> {
+    let mut args = vec![Cow::from("ls-files"), "-z".into(), "--".into()];
+    args.extend(paths.iter().map(|path| path.to_string_lossy()));
+    let files = Cmd::new("git", args).output(work_dir).await.ok()?;
+    let files = files.split('\0').filter(|file| !file.is_empty());
+    let files = files.map(Path::new).collect::<Vec<_>>();
+    return paths
+        .iter()
+        .find(|path| files.iter().any(|file| file.starts_with(path)));
+}
+
+/// The first of the `paths` relative to the `work_dir` which git doesn't
+/// ignore, i.e. which `git clean -X` would not remove, by a single git call.
+pub(crate) async fn unignored<P: AsRef<Path>>(work_dir: P, paths: &[PathBuf]) -> Option<&PathBuf> {
+    let mut args = vec![Cow::from("check-ignore"), "--".into()];
+    args.extend(paths.iter().map(|path| path.to_string_lossy()));
+    // exits with 1 if none of the paths is ignored, the quoted unusual paths
+    // are taken as not ignored, as `-z` requires `--stdin`
+    let ignored = Cmd::new("git", args)
+        .output(work_dir)
+        .await
+        .unwrap_or_default();
+    let ignored = ignored.lines().map(Path::new).collect::<Vec<_>>();
+    return paths.iter().find(|path| !ignored.contains(&path.as_path()));
+}
+
+/// Whether the work tree under the `work_dir` has uncommitted changes,
+/// `false` if the work dir is not inside a repository or git is unavailable.
+pub(crate) async fn dirty<P: AsRef<Path>>(work_dir: P) -> bool {
+    let status = Cmd::new("git", ["status", "--porcelain", "--", "."]);
+    return status
+        .output(work_dir)
+        .await
+        .is_ok_and(|changes| !changes.is_empty());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, process::Command};
+
+    use super::{dirty, tracked, unignored};
+    use crate::test::{temp_dir, RmDirGuard};
+
+    #[tokio::test]
+    async fn tracked_ignored_and_dirty_paths() {
+        let repo = temp_dir("clean-git");
+        let _guard = RmDirGuard(&repo);
+        fs::create_dir_all(repo.join("target")).unwrap();
+        fs::create_dir_all(repo.join("build")).unwrap();
+        fs::write(repo.join(".gitignore"), "target/\n").unwrap();
+        fs::write(repo.join("target/a"), "").unwrap();
+        fs::write(repo.join("build/b"), "").unwrap();
+
+        let (target, build) = (PathBuf::from("target"), PathBuf::from("build"));
+        let outputs = [target.clone(), build.clone()];
+        assert_eq!(tracked(&repo, &outputs).await, None);
+        assert_eq!(unignored(&repo, &outputs).await, Some(&target));
+        assert!(!dirty(&repo).await);
+
+        let git = |args: &[&str]| Command::new("git").args(args).current_dir(&repo).output();
+        if git(&["init", "-q"]).is_err() {
+            return;
+        }
+        git(&["add", "build", ".gitignore"]).unwrap();
+
+        assert_eq!(tracked(&repo, &outputs).await, Some(&build));
+        assert_eq!(tracked(&repo, &outputs[..1]).await, None);
+        assert_eq!(unignored(&repo, &outputs).await, Some(&build));
+        assert_eq!(unignored(&repo, &outputs[..1]).await, None);
+        assert!(dirty(&repo).await);
+        assert!(!dirty(repo.join("target")).await);
+    }
+}
@@ -1,28 +1,42 @@
 #![allow(clippy::needless_return)]
 #![doc = include_str!("../README.md")]
 
-use std::{
-    io::Write,
-    path::{Path, PathBuf},
-    sync::Arc,
-};
+use std::{path::Path, sync::Arc};
 
-use async_recursion::async_recursion;
-use conf::{Config, Plan};
-use futures::future::{try_join_all, TryJoinAll};
+use conf::Config;
+use exec::Execution;
+use futures::{
+    future::{try_join_all, TryJoinAll},
+    stream, StreamExt,
+};
 use tokio::{
-    fs,
     sync::{
-        mpsc::{self, Receiver, Sender},
+        mpsc::{self, Receiver},
         Mutex,
     },
     task::JoinHandle,
 };
+use trash::Trash;
+use walk::Walker;
 
+mod builtin;
+mod cache;
 mod cmd;
 pub mod conf;
+mod disk;
 mod error;
+mod exec;
+mod git;
+mod opts;
+mod prompt;
+mod trash;
+mod walk;
+pub use disk::Bytes;
 pub use error::Error;
+pub use opts::Options;
+
+#[cfg(test)]
+mod test;
 
 pub(crate) type IOResult<T> = std::io::Result<T>;
 pub type Result<T> = anyhow::Result<T>;
@@ -34,148 +48,200 @@ where
     clean_with_config(entry, Config::home().await?).await
 }
 
-pub async fn clean_with_config<P>(entry: P, config: Config) -> Result<bool>
+pub async fn clean_with_config<P>(entry: P, mut config: Config) -> Result<bool>
 where
     P: AsRef<Path>,
 {
-    assert_dir_exists(entry.as_ref())?;
-    let ncpus = num_cpus::get();
-    let (tx, rx) = mpsc::channel::<Execution>(ncpus);
+    let entry = entry.as_ref();
+    assert_dir_exists(entry)?;
+    if !config.options.force {
+        assert_not_protected(entry, &config)?;
+    }
+    let trash = Trash::new(&config.options)?;
+    if let Some(trash) = &trash {
+        config.protect(trash.root());
+    }
 
-    let tasks = spawn((ncpus >> 1).max(1), Arc::new(Mutex::new(rx)));
-    collect(entry, Arc::new(config), tx).await?;
+    let mut executions = Walker::walk(entry, &config).await?;
+    for exe in executions.iter_mut() {
+        exe.trash = trash.clone();
+    }
+    if config.options.measures() {
+        let sizes = try_join_all(executions.iter().map(Execution::reclaimable)).await?;
+        for (exe, size) in executions.iter_mut().zip(sizes) {
+            exe.size = size;
+        }
+    }
+    // each reason may run git, bounded like the cleaning workers
+    let reasons = stream::iter(executions.iter().map(|exe| exe.skip_reason(&config)))
+        .buffered(num_cpus::get())
+        .collect::<Vec<_>>()
+        .await;
+    let (executions, skipped): (Vec<_>, Vec<_>) = reasons
+        .into_iter()
+        .zip(executions)
+        .partition(|(reason, _)| reason.is_none());
+    for (reason, exe) in skipped {
+        exe.skip(&reason.unwrap_or_default())?;
+    }
+
+    let mut executions = executions
+        .into_iter()
+        .map(|(_, exe)| exe)
+        .collect::<Vec<_>>();
+    if config.options.go_cache {
+        let go = cache::go(entry).await;
+        match go.measured.is_empty() {
+            true => go.skip("go is not available")?,
+            // confirmed or selected along with the projects, but runs after them
+            false => executions.push(go),
+        }
+    }
+    if config.options.interactive {
+        let input = tokio::io::BufReader::new(tokio::io::stdin());
+        executions = prompt::select(executions, input, std::io::stdout()).await?;
+    } else if !confirmed(&executions, "project", &config).await? {
+        return Ok(false);
+    }
 
-    return tasks
-        .await?
+    // only the go cache measures the space freed by its run
+    let (go, executions): (Vec<_>, Vec<_>) = executions
         .into_iter()
-        .try_fold(true, |status, result| result.map(|each| each || status));
+        .partition(|exe| !exe.measured.is_empty());
+    let mut clean = match config.options.free {
+        Some(free) => free_up(entry, executions, free).await?,
+        None => execute(executions).await?,
+    };
+    for go in go {
+        clean = go.run().await? && clean;
+    }
+    return Ok(clean);
+
+    async fn execute(executions: Vec<Execution<'static>>) -> Result<bool> {
+        let ncpus = num_cpus::get();
+        let (tx, rx) = mpsc::channel::<Execution>(ncpus);
+        let tasks = spawn((ncpus >> 1).max(1), Arc::new(Mutex::new(rx)));
+        for exe in executions {
+            let _ = tx.send(exe).await;
+        }
+        drop(tx);
+
+        return tasks
+            .await?
+            .into_iter()
+            .try_fold(true, |status, result| result.map(|each| each || status));
+    }
+
+    /// Runs the largest and stalest executions first, one at a time, until the
+    /// filesystem holding the `entry` has the `free` space available.
+    async fn free_up(
+        entry: &Path,
+        mut executions: Vec<Execution<'static>>,
+        free: Bytes,
+    ) -> Result<bool> {
+        executions.sort_by(|a, b| b.rank().total_cmp(&a.rank()));
+        let mut clean = true;
+        for exe in executions {
+            if disk::available(entry)? >= free.0 {
+                break;
+            }
+            clean = exe.run().await? && clean;
+        }
+        return Ok(clean);
+    }
 
     type ExecutionRecv = Arc<Mutex<Receiver<Execution<'static>>>>;
     fn spawn(n: usize, rx: ExecutionRecv) -> TryJoinAll<JoinHandle<Result<bool>>> {
         try_join_all((0..n).map(move |_| {
             let rx = rx.clone();
             tokio::spawn(async move {
-                let mut clean = false;
+                // the worker keeps running the rest of the queue on errors,
+                // which are reported once it's drained
+                let (mut clean, mut error) = (false, None);
                 while let Some(execution) = rx.lock().await.recv().await {
-                    clean = execution.run().await? || clean;
+                    match execution.run().await {
+                        Ok(each) => clean = each || clean,
+                        Err(err) => error = error.or(Some(err)),
+                    }
                 }
-                Result::Ok(clean)
+                error.map_or(Ok(clean), Err)
             })
         }))
     }
 
     fn assert_dir_exists(path: &Path) -> Result<()> {
         if !path.exists() {
-            return Err(Error::other(format!(
-                "Directory not found: {}",
-                path.display()
-            )))?;
+            return Err(Error::other(format!("Directory not found: {}", path.display())).into());
         }
         if !path.is_dir() {
+            return Err(Error::other(format!("{} is not a directory", path.display())).into());
+        }
+        Ok(())
+    }
+
+    fn assert_not_protected(path: &Path, config: &Config) -> Result<()> {
+        let path = conf::normalize(path);
+        let home = home::home_dir().map(conf::normalize);
+        if path.parent().is_none() || Some(&path) == home.as_ref() || config.protects(&path) {
             return Err(Error::other(format!(
-                "{} is not a directory",
+                "{} is protected, use --force to clean it anyway",
                 path.display()
-            )))?;
+            ))
+            .into());
         }
         Ok(())
     }
 }
 
-#[async_recursion(?Send)]
-async fn collect<P>(entry: P, config: Arc<Config>, tx: Sender<Execution<'static>>) -> IOResult<()>
-where
-    P: AsRef<Path>,
-{
-    macro_rules! try_unwrap {
-        ($exp: expr) => {
-            match $exp {
-                Ok(value) => value,
-                Err(err) => match err.kind() {
-                    std::io::ErrorKind::NotFound => return Ok(()),
-                    _ => return Err(err),
-                },
-            }
-        };
+/// Cleans the toolchain caches under the home directory, e.g: `~/.cargo/registry`,
+/// `~/.m2/repository`, rather than the projects.
+pub async fn clean_caches(config: Config) -> Result<bool> {
+    let mut prunes = vec![];
+    for (cache, layout) in cache::discover() {
+        let mut prune = cache::prune(cache, layout, config.options.older_than);
+        prune.size = prune.reclaimable().await?;
+        prune.found()?;
+        prunes.push(prune);
     }
-    let entry = entry.as_ref();
-    let mut dir = try_unwrap!(fs::read_dir(entry).await);
-
-    while let Some(current) = try_unwrap!(dir.next_entry().await).map(|e| e.path()) {
-        if let Some(plan) = config.parse(&current) {
-            let _ = tx.send(Execution(plan, entry.to_owned())).await;
-        }
-        if current.is_dir() {
-            collect(current, config.clone(), tx.clone()).await?;
-        }
+    if !confirmed(&prunes, "cache", &config).await? {
+        return Ok(false);
     }
-
-    return Ok(());
+    let mut clean = true;
+    for prune in prunes {
+        clean = prune.run().await? && clean;
+    }
+    return Ok(clean);
 }
 
-#[derive(Debug, Clone)]
-struct Execution<'a>(Plan<'a>, PathBuf);
-
-impl<'a> Execution<'a> {
-    async fn run(&self) -> Result<bool> {
-        let result = self.0.run(&self.1).await;
-        write(self, &result)?;
-
-        return result;
-
-        use termcolor::{
-            Buffer, BufferWriter, Color, ColorChoice, ColorSpec, HyperlinkSpec, WriteColor,
-        };
-        fn write(exe: &Execution, result: &Result<bool>) -> Result<()> {
-            use std::io::{stdout, IsTerminal};
-            let out = BufferWriter::stdout(match stdout().is_terminal() {
-                true => ColorChoice::Always,
-                _ => ColorChoice::Never,
-            });
-            return Ok(out.print(&try_concat(tag(exe, &out), colorized(result, &out))?)?);
-        }
-
-        fn try_concat(head: IOResult<Buffer>, tail: IOResult<Buffer>) -> IOResult<Buffer> {
-            let mut buf = Buffer::ansi();
-            buf.write_all(head?.as_slice())?;
-            buf.write_all(tail?.as_slice())?;
-            buf.write_all(b"\n")?;
-            return Ok(buf);
-        }
-
-        fn tag(exe: &Execution, out: &BufferWriter) -> IOResult<Buffer> {
-            let mut buf = colorized_text(exe.0.cmd().as_ref(), Color::Cyan, out)?;
-            let url = {
-                use path_absolutize::Absolutize;
-                let url = format!("file://{}", exe.1.absolutize()?.display());
-                #[cfg(target_os = "windows")]
-                let url = url.replace('\\', "/");
-                url
-            };
-            write!(buf, " clean: ")?;
-            buf.set_hyperlink(&HyperlinkSpec::open(url.as_bytes()))?;
-            write!(buf, "{}", exe.1.display())?;
-            buf.set_hyperlink(&HyperlinkSpec::close())?;
-            write!(buf, "? ")?;
-            return Ok(buf);
-        }
+/// Empties the trash or the quarantine directory chosen by the options, only
+/// the entries moved there before `older_than` if present.
+pub async fn purge(config: Config) -> Result<bool> {
+    let Some(trash) = Trash::new(&config.options)? else {
+        return Err(Error::other("nothing to purge, use --trash or --quarantine").into());
+    };
+    let mut purge = trash::purge(trash, config.options.older_than);
+    purge.size = purge.reclaimable().await?;
+    if !confirmed(std::slice::from_ref(&purge), "trash", &config).await? {
+        return Ok(false);
+    }
+    return purge.run().await;
+}
 
-        fn colorized(result: &Result<bool>, out: &BufferWriter) -> IOResult<Buffer> {
-            let (fg, text) = if let Ok(true) = result {
-                (Color::Green, "ok")
-            } else {
-                (Color::Red, "error")
-            };
-            return colorized_text(text, fg, out);
-        }
+/// Moves the directories removed by the latest run back from the quarantine
+/// directory chosen by the options, the recreated ones are reported as conflicts.
+pub async fn restore(config: Config) -> Result<bool> {
+    match Trash::new(&config.options)? {
+        Some(Trash::Quarantine { root, .. }) => trash::restore(&root).await,
+        _ => Err(Error::other("nothing to restore, use --quarantine"))?,
+    }
+}
 
-        fn colorized_text(text: &str, fg: Color, out: &BufferWriter) -> IOResult<Buffer> {
-            let mut buf = out.buffer();
-            let mut spec = ColorSpec::new();
-            buf.set_color(spec.set_fg(Some(fg)))?;
-            write!(buf, "{}", text)?;
-            spec.clear();
-            buf.set_color(&spec)?;
-            return Ok(buf);
-        }
+/// Summarizes the `executions` of the `kind` and asks the user to confirm if
+/// the options require, nothing is confirmed if empty.
+async fn confirmed(executions: &[Execution<'_>], kind: &str, config: &Config) -> Result<bool> {
+    if !config.options.confirm || executions.is_empty() {
+        return Ok(true);
     }
+    let input = tokio::io::BufReader::new(tokio::io::stdin());
+    return prompt::confirm(executions, kind, input, std::io::stdout()).await;
 }
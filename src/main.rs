@@ -1,6 +1,45 @@
-use std::process::exit;
+use std::{io::IsTerminal, process::exit, str::FromStr, time::Duration};
 
-use clean_rs::{clean, Result};
+use clean_rs::{
+    clean_caches, clean_with_config, conf::Config, purge, restore, Error, Options, Result,
+};
+
+const USAGE: &str = "\
+Usage: clean [OPTIONS] [DIR]
+
+Arguments:
+  [DIR]  Directory to clean [default: .]
+
+Options:
+      --caches             Clean the toolchain caches under the home directory instead
+      --purge              Empty the trash or quarantine directory instead, see --older-than
+      --restore            Move the directories removed by the latest run back from the quarantine
+      --older-than <DAYS>  Only clean the projects or caches not modified for the given days
+      --min-size <SIZE>    Only clean the projects whose build outputs are at least the size, e.g: 100M
+      --free <SIZE>        Clean the largest and stalest projects first until the size is free, e.g: 50G
+  -y, --yes                Clean without confirmation
+  -i, --interactive        Select the projects to clean in the terminal
+      --force              Clean the root, home or protected directory anyway
+  -L, --follow-symlinks    Descend into the directories behind symlinks
+  -x, --one-file-system    Don't descend into the directories on other file systems, unix only
+      --min-depth <N>      Only clean the projects at least N levels below the start directory
+      --max-depth <N>      Only clean the projects at most N levels below the start directory
+      --include <GLOB>     Only clean the paths matching the glob, can be repeated
+      --exclude <GLOB>     Don't clean the paths matching the glob, can be repeated
+      --only <NAMES>       Only clean by the rules of the comma separated names, e.g: cargo,node
+      --skip <NAMES>       Don't clean by the rules of the comma separated names, e.g: gradle
+      --git-ignored        Only clean the build outputs ignored by git, like `git clean -X`
+      --active-within <MINUTES>
+                           Skip the projects whose build outputs are written within the minutes [default: 10]
+      --skip-dirty         Skip the projects with uncommitted changes in git
+      --trash              Move the removed directories into the trash rather than deleting them
+      --quarantine <DIR>   Move the removed directories into the directory on the same file system
+      --go-cache           Clean the go build, test and module caches once per run
+  -h, --help               Print help
+";
+
+/// Build outputs written within this duration are likely being built.
+const ACTIVE_WITHIN: Duration = Duration::from_secs(10 * 60);
 
 #[tokio::main]
 async fn main() {
@@ -11,7 +50,334 @@ async fn main() {
 }
 
 async fn run() -> Result<()> {
-    let start_dir = std::env::args().nth(1);
-    clean(start_dir.as_deref().unwrap_or(".")).await?;
+    let Some(args) = parse(std::env::args().skip(1))? else {
+        print!("{USAGE}");
+        return Ok(());
+    };
+    let mut config = Config::home().await?;
+    for warning in config.warnings() {
+        eprintln!("warning: {warning}");
+    }
+    config.options = args.options;
+    config.options.confirm = !args.yes && std::io::stdin().is_terminal();
+    if args.caches {
+        clean_caches(config).await?;
+        return Ok(());
+    }
+    if args.purge {
+        purge(config).await?;
+        return Ok(());
+    }
+    if args.restore {
+        restore(config).await?;
+        return Ok(());
+    }
+    clean_with_config(args.start_dir.as_deref().unwrap_or("."), config).await?;
     Ok(())
 }
+
+#[derive(Debug, Default)]
+struct Args {
+    start_dir: Option<String>,
+    caches: bool,
+    purge: bool,
+    restore: bool,
+    yes: bool,
+    options: Options,
+}
+
+/// Parses the command line arguments, returns `None` if help is requested.
+fn parse<I>(args: I) -> Result<Option<Args>>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut parsed = Args::default();
+    parsed.options.active_within = Some(ACTIVE_WITHIN);
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, mut value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || value.take().or_else(|| args.next());
+        let options = &mut parsed.options;
+        match flag {
+            "-h" | "--help" => return Ok(None),
+            "--caches" => parsed.caches = true,
+            "--purge" => parsed.purge = true,
+            "--restore" => parsed.restore = true,
+            "-y" | "--yes" => parsed.yes = true,
+            "--older-than" => {
+                let days = required::<u64>(flag, value())?;
+                options.older_than = Some(Duration::from_secs(secs(flag, days, 24 * 60 * 60)?));
+            }
+            "--min-size" => options.min_size = Some(required(flag, value())?),
+            "--free" => options.free = Some(required(flag, value())?),
+            "-i" | "--interactive" => options.interactive = true,
+            "--force" => options.force = true,
+            "-L" | "--follow-symlinks" => options.follow_symlinks = true,
+            "-x" | "--one-file-system" => options.one_file_system = true,
+            "--min-depth" => options.min_depth = required(flag, value())?,
+            "--max-depth" => options.max_depth = Some(required(flag, value())?),
+            "--include" => options.include.push(required(flag, value())?),
+            "--exclude" => options.exclude.push(required(flag, value())?),
+            "--only" => options.only.extend(names(required(flag, value())?)),
+            "--skip" => options.skip.extend(names(required(flag, value())?)),
+            "--git-ignored" => options.git_ignored = true,
+            "--active-within" => {
+                let minutes = required::<u64>(flag, value())?;
+                options.active_within = Some(Duration::from_secs(secs(flag, minutes, 60)?));
+            }
+            "--skip-dirty" => options.skip_dirty = true,
+            "--trash" => options.trash = true,
+            "--quarantine" => options.quarantine = Some(required(flag, value())?),
+            "--go-cache" => options.go_cache = true,
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                Err(Error::other(format!("unknown option: `{flag}`\n\n{USAGE}")))?
+            }
+            _ if parsed.start_dir.is_some() => Err(Error::other(format!(
+                "unexpected argument: `{arg}`\n\n{USAGE}"
+            )))?,
+            _ => parsed.start_dir = Some(arg),
+        }
+    }
+    return Ok(Some(parsed));
+
+    fn names(names: String) -> impl Iterator<Item = String> {
+        let names = names.split(',').map(str::trim).map(String::from);
+        names
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Seconds of the `n` units of the `unit` seconds, fails on overflow.
+    fn secs(flag: &str, n: u64, unit: u64) -> Result<u64> {
+        match n.checked_mul(unit) {
+            Some(secs) => Ok(secs),
+            None => Err(Error::other(format!("invalid value `{n}` for `{flag}`")))?,
+        }
+    }
+
+    fn required<T: FromStr>(flag: &str, value: Option<String>) -> Result<T> {
+        match value.as_deref().map(str::parse) {
+            Some(Ok(value)) => Ok(value),
+            Some(Err(_)) => Err(Error::other(format!(
+                "invalid value `{}` for `{flag}`",
+                value.unwrap_or_default()
+            )))?,
+            None => Err(Error::other(format!("a value is required for `{flag}`")))?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use clean_rs::Bytes;
+
+    use super::parse;
+
+    fn argv(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_start_dir() {
+        let args = parse(argv(&["/home"])).unwrap().unwrap();
+        assert_eq!(args.start_dir.as_deref(), Some("/home"));
+        assert!(!args.options.go_cache);
+        assert!(!args.caches);
+
+        let args = parse(vec![]).unwrap().unwrap();
+        assert_eq!(args.start_dir, None);
+    }
+
+    #[test]
+    fn parse_go_cache() {
+        let args = parse(argv(&["--go-cache", "."])).unwrap().unwrap();
+        assert_eq!(args.start_dir.as_deref(), Some("."));
+        assert!(args.options.go_cache);
+    }
+
+    #[test]
+    fn parse_caches_older_than() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        let args = parse(argv(&["--caches", "--older-than", "30"]))
+            .unwrap()
+            .unwrap();
+        assert!(args.caches);
+        assert_eq!(args.options.older_than, Some(day * 30));
+
+        let args = parse(argv(&["--older-than=7"])).unwrap().unwrap();
+        assert_eq!(args.options.older_than, Some(day * 7));
+    }
+
+    #[test]
+    fn parse_min_size() {
+        let args = parse(argv(&["--min-size", "100M"])).unwrap().unwrap();
+        assert_eq!(args.options.min_size, Some(Bytes(100 << 20)));
+
+        let err = parse(argv(&["--min-size", "100X"])).unwrap_err();
+        assert_eq!(err.to_string(), "invalid value `100X` for `--min-size`");
+    }
+
+    #[test]
+    fn parse_free() {
+        let args = parse(argv(&["--free=50G", "/"])).unwrap().unwrap();
+        assert_eq!(args.options.free, Some(Bytes(50 << 30)));
+        assert_eq!(args.start_dir.as_deref(), Some("/"));
+    }
+
+    #[test]
+    fn parse_interactive() {
+        assert!(parse(argv(&["-i"])).unwrap().unwrap().options.interactive);
+        let args = parse(argv(&["--interactive"])).unwrap().unwrap();
+        assert!(args.options.interactive);
+    }
+
+    #[test]
+    fn parse_yes() {
+        assert!(!parse(argv(&[])).unwrap().unwrap().yes);
+        assert!(parse(argv(&["-y"])).unwrap().unwrap().yes);
+        assert!(parse(argv(&["--yes"])).unwrap().unwrap().yes);
+    }
+
+    #[test]
+    fn parse_force() {
+        assert!(!parse(argv(&["/"])).unwrap().unwrap().options.force);
+        assert!(
+            parse(argv(&["--force", "/"]))
+                .unwrap()
+                .unwrap()
+                .options
+                .force
+        );
+    }
+
+    #[test]
+    fn parse_traversal_policy() {
+        let args = parse(argv(&[])).unwrap().unwrap();
+        assert!(!args.options.follow_symlinks);
+        assert!(!args.options.one_file_system);
+
+        let args = parse(argv(&["-L", "-x"])).unwrap().unwrap();
+        assert!(args.options.follow_symlinks);
+        assert!(args.options.one_file_system);
+
+        let args = parse(argv(&["--follow-symlinks", "--one-file-system"]))
+            .unwrap()
+            .unwrap();
+        assert!(args.options.follow_symlinks);
+        assert!(args.options.one_file_system);
+    }
+
+    #[test]
+    fn parse_depths() {
+        let args = parse(argv(&[])).unwrap().unwrap();
+        assert_eq!(args.options.min_depth, 0);
+        assert_eq!(args.options.max_depth, None);
+
+        let args = parse(argv(&["--min-depth", "1", "--max-depth=2"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(args.options.min_depth, 1);
+        assert_eq!(args.options.max_depth, Some(2));
+    }
+
+    #[test]
+    fn parse_repeatable_globs() {
+        let args = parse(argv(&["--include", "**/a", "--exclude=b", "--include=c/*"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(args.options.include, ["**/a", "c/*"]);
+        assert_eq!(args.options.exclude, ["b"]);
+    }
+
+    #[test]
+    fn parse_rule_names() {
+        let args = parse(argv(&[
+            "--only",
+            "cargo, node_modules",
+            "--only=go",
+            "--skip",
+            "gradle",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(args.options.only, ["cargo", "node_modules", "go"]);
+        assert_eq!(args.options.skip, ["gradle"]);
+    }
+
+    #[test]
+    fn parse_git_ignored() {
+        assert!(!parse(argv(&[])).unwrap().unwrap().options.git_ignored);
+        let args = parse(argv(&["--git-ignored"])).unwrap().unwrap();
+        assert!(args.options.git_ignored);
+    }
+
+    #[test]
+    fn parse_activity() {
+        let args = parse(argv(&[])).unwrap().unwrap();
+        assert_eq!(args.options.active_within, Some(Duration::from_secs(600)));
+        assert!(!args.options.skip_dirty);
+
+        let args = parse(argv(&["--active-within=0", "--skip-dirty"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(args.options.active_within, Some(Duration::ZERO));
+        assert!(args.options.skip_dirty);
+
+        let max = format!("--active-within={}", u64::MAX);
+        assert!(parse(argv(&[&max])).is_err());
+    }
+
+    #[test]
+    fn parse_trash() {
+        let args = parse(argv(&["--trash"])).unwrap().unwrap();
+        assert!(args.options.trash);
+        assert!(!args.purge);
+
+        let args = parse(argv(&["--purge", "--quarantine", "/q", "--older-than=7"]))
+            .unwrap()
+            .unwrap();
+        assert!(args.purge);
+        assert_eq!(args.options.quarantine, Some("/q".into()));
+
+        let args = parse(argv(&["--restore", "--quarantine=/q"]))
+            .unwrap()
+            .unwrap();
+        assert!(args.restore);
+    }
+
+    #[test]
+    fn parse_help() {
+        assert!(parse(argv(&["-h"])).unwrap().is_none());
+        assert!(parse(argv(&[".", "--help"])).unwrap().is_none());
+    }
+
+    #[test]
+    fn fails_on_unknown_arguments() {
+        let err = parse(argv(&["--unknown"])).unwrap_err();
+        assert!(err.to_string().starts_with("unknown option: `--unknown`"));
+
+        let err = parse(argv(&["a", "b"])).unwrap_err();
+        assert!(err.to_string().starts_with("unexpected argument: `b`"));
+    }
+
+    #[test]
+    fn fails_on_invalid_values() {
+        let err = parse(argv(&["--older-than"])).unwrap_err();
+        assert_eq!(err.to_string(), "a value is required for `--older-than`");
+
+        let err = parse(argv(&["--older-than", "x"])).unwrap_err();
+        assert_eq!(err.to_string(), "invalid value `x` for `--older-than`");
+
+        let err = parse(argv(&["--older-than", &u64::MAX.to_string()])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("invalid value `{}` for `--older-than`", u64::MAX)
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::Bytes;
+
+/// Options of a clean run, usually given on the command line.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Run `go clean -cache -testcache -modcache` once per run.
+    pub go_cache: bool,
+    /// Only clean the content which is not modified within this duration.
+    pub older_than: Option<Duration>,
+    /// Only clean the projects whose build outputs are at least this size, the
+    /// projects whose build outputs are unknown are skipped.
+    pub min_size: Option<Bytes>,
+    /// Clean the largest and stalest projects first, only until this much space is free.
+    pub free: Option<Bytes>,
+    /// Let the user select the projects to clean in the terminal.
+    pub interactive: bool,
+    /// Summarize the projects and ask the user to confirm before cleaning.
+    pub confirm: bool,
+    /// Clean the root, home or protected directory as the start directory.
+    pub force: bool,
+    /// Descend into the directories behind symlinks.
+    pub follow_symlinks: bool,
+    /// Don't descend into the directories on other file systems, Unix only.
+    pub one_file_system: bool,
+    /// Only clean the projects at least this deep, the start directory is at depth 0.
+    pub min_depth: usize,
+    /// Only clean the projects at most this deep.
+    pub max_depth: Option<usize>,
+    /// Only clean the paths matching any of the globs, all paths if empty.
+    pub include: Vec<String>,
+    /// Don't clean the paths matching any of the globs.
+    pub exclude: Vec<String>,
+    /// Only clean by the rules of the names, e.g: `cargo`, `node_modules`, all rules if empty.
+    pub only: Vec<String>,
+    /// Don't clean by the rules of the names.
+    pub skip: Vec<String>,
+    /// Only clean the build outputs ignored by git, like `git clean -X`.
+    pub git_ignored: bool,
+    /// Skip the projects whose build outputs are written within this duration,
+    /// as they are likely being built.
+    pub active_within: Option<Duration>,
+    /// Skip the projects with uncommitted changes in git.
+    pub skip_dirty: bool,
+    /// Move the removed directories into the freedesktop trash rather than
+    /// deleting them permanently.
+    pub trash: bool,
+    /// Move the removed directories into this directory rather than deleting
+    /// them permanently, takes precedence over `trash`.
+    pub quarantine: Option<PathBuf>,
+}
+
+impl Options {
+    /// Whether the reclaimable sizes of the projects are needed.
+    pub(crate) fn measures(&self) -> bool {
+        self.min_size.is_some() || self.free.is_some() || self.interactive
+    }
+}
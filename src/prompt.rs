@@ -0,0 +1,243 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+    ops::RangeInclusive,
+};
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::{disk::Bytes, exec::Execution, Result};
+
+const HELP: &str = "\
+Toggle by numbers or ranges, e.g: `1 3-5`, `a` for all, `n` for none,
+press enter to clean the selected, `q` to quit: ";
+
+/// Lists the `executions` and lets the user toggle which of them to run, all of
+/// them are selected initially. Nothing is selected if the user quits.
+pub(crate) async fn select<'a, R, W>(
+    executions: Vec<Execution<'a>>,
+    mut input: R,
+    mut output: W,
+) -> Result<Vec<Execution<'a>>>
+where
+    R: AsyncBufRead + Unpin,
+    W: Write,
+{
+    let mut selected = (0..executions.len()).collect::<BTreeSet<_>>();
+    loop {
+        for (i, exe) in executions.iter().enumerate() {
+            let size = exe
+                .size
+                .map_or("?".to_string(), |size| Bytes(size).to_string());
+            writeln!(
+                output,
+                "[{}] {:>3}  {:<8} {:>10}  {}",
+                if selected.contains(&i) { 'x' } else { ' ' },
+                i + 1,
+                exe.plan.cmd(),
+                size,
+                exe.work_dir.display()
+            )?;
+        }
+        write!(output, "{HELP}")?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line).await? == 0 {
+            return Ok(vec![]);
+        }
+        match line.trim() {
+            "" => break,
+            "q" => return Ok(vec![]),
+            "a" => selected.extend(0..executions.len()),
+            "n" => selected.clear(),
+            line => match parse(line, executions.len()) {
+                Some(toggled) => {
+                    for i in toggled.into_iter().flatten() {
+                        if !selected.remove(&i) {
+                            selected.insert(i);
+                        }
+                    }
+                }
+                None => writeln!(output, "invalid selection: `{line}`")?,
+            },
+        }
+    }
+
+    return Ok(executions
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, exe)| selected.contains(&i).then_some(exe))
+        .collect());
+
+    /// Parses 1-based numbers and ranges into 0-based ranges bounded by `len`.
+    fn parse(line: &str, len: usize) -> Option<Vec<RangeInclusive<usize>>> {
+        line.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                let (start, end) = part.split_once('-').unwrap_or((part, part));
+                let (start, end) = (start.parse::<usize>().ok()?, end.parse::<usize>().ok()?);
+                (1 <= start && start <= end && end <= len).then(|| start - 1..=end - 1)
+            })
+            .collect()
+    }
+}
+
+/// Summarizes the `executions` of the `kind`, e.g: `project`, and asks the
+/// user to confirm, defaults to no.
+pub(crate) async fn confirm<R, W>(
+    executions: &[Execution<'_>],
+    kind: &str,
+    mut input: R,
+    mut output: W,
+) -> Result<bool>
+where
+    R: AsyncBufRead + Unpin,
+    W: Write,
+{
+    let dirs = executions
+        .iter()
+        .map(|exe| &exe.work_dir)
+        .collect::<BTreeSet<_>>();
+    let mut tools = BTreeMap::<&str, usize>::new();
+    for exe in executions {
+        *tools.entry(exe.plan.cmd()).or_default() += 1;
+    }
+    let rm = executions
+        .iter()
+        .filter(|exe| exe.trash.is_none())
+        .map(|exe| exe.plan.removes().len())
+        .sum::<usize>();
+    let size = executions.iter().filter_map(|exe| exe.size).sum::<u64>();
+
+    writeln!(output, "About to clean {} {kind}(s):", dirs.len())?;
+    for (tool, n) in tools {
+        writeln!(output, "  {tool:<8} {n:>5}")?;
+    }
+    if rm > 0 {
+        writeln!(output, "{rm} directory(s) will be removed permanently.")?;
+    }
+    if size > 0 {
+        writeln!(output, "{} found.", Bytes(size))?;
+    }
+    write!(output, "Proceed? [y/N] ")?;
+    output.flush()?;
+
+    let mut line = String::new();
+    input.read_line(&mut line).await?;
+    return Ok(matches!(line.trim(), "y" | "Y" | "yes" | "Yes" | "YES"));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{confirm, select};
+    use crate::{conf::Plan, exec::Execution};
+
+    fn executions() -> Vec<Execution<'static>> {
+        ["a", "b", "c", "d"]
+            .iter()
+            .map(|dir| Execution::new(Plan::RmDir("target".into()), PathBuf::from(dir)))
+            .collect()
+    }
+
+    async fn selected(input: &str) -> Vec<String> {
+        let mut output = vec![];
+        let selected = select(executions(), input.as_bytes(), &mut output)
+            .await
+            .unwrap();
+        selected
+            .iter()
+            .map(|exe| exe.work_dir.display().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn select_all_by_default() {
+        assert_eq!(selected("\n").await, ["a", "b", "c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn toggle_numbers_and_ranges() {
+        assert_eq!(selected("1 3-4\n\n").await, ["b"]);
+        assert_eq!(selected("n\n2,4\n\n").await, ["b", "d"]);
+        assert_eq!(selected("n\n1\na\n\n").await, ["a", "b", "c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn ignore_invalid_selection() {
+        assert_eq!(selected("0\n5\n2-1\nx\n2\n\n").await, ["a", "c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn select_nothing_on_quit() {
+        assert!(selected("1\nq\n").await.is_empty());
+        assert!(selected("1\n").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn list_executions() {
+        let mut output = vec![];
+        let mut executions = executions();
+        executions[0].size = Some(2048);
+        select(executions, "q\n".as_bytes(), &mut output)
+            .await
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let mut lines = output.lines();
+        assert_eq!(lines.next().unwrap(), "[x]   1  rm          2.0 KiB  a");
+        assert_eq!(lines.next().unwrap(), "[x]   2  rm                ?  b");
+    }
+
+    #[tokio::test]
+    async fn summarize_before_confirm() {
+        let mut executions = executions();
+        executions.push(Execution::new(
+            Plan::Cmd("Cargo.toml".parse().unwrap()),
+            "a".into(),
+        ));
+        let dotnet = Plan::RmDir("bin".into()).then(Plan::RmDir("obj".into()), false);
+        executions.push(Execution::new(dotnet, "e".into()));
+        let mut output = vec![];
+
+        assert!(
+            confirm(&executions, "project", "y\n".as_bytes(), &mut output)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\
+About to clean 5 project(s):
+  cargo        1
+  rm           5
+6 directory(s) will be removed permanently.
+Proceed? [y/N] "
+        );
+    }
+
+    #[tokio::test]
+    async fn summarize_found_size() {
+        let mut executions = executions();
+        executions[0].size = Some(1024);
+        executions[1].size = Some(1024);
+        let mut output = vec![];
+
+        confirm(&executions[..2], "cache", "\n".as_bytes(), &mut output)
+            .await
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("About to clean 2 cache(s):\n"));
+        assert!(output.contains("\n2.0 KiB found.\n"));
+    }
+
+    #[tokio::test]
+    async fn refuse_by_default() {
+        let executions = executions();
+        for input in ["\n", "n\n", "no\n", ""] {
+            let confirmed = confirm(&executions, "project", input.as_bytes(), &mut vec![]).await;
+            assert!(!confirmed.unwrap());
+        }
+    }
+}
@@ -5,3 +5,12 @@ impl<T: AsRef<std::path::Path>> Drop for RmDirGuard<T> {
         let _ = std::fs::remove_dir_all(self.0.as_ref());
     }
 }
+
+/// A unique path under the temp dir starts with the `prefix`, e.g: `test-walk-<nanos>`.
+pub fn temp_dir(prefix: &str) -> std::path::PathBuf {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    std::env::temp_dir().join(format!("{prefix}-{nanos}"))
+}
@@ -0,0 +1,653 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    conf::{self, Plan},
+    disk,
+    exec::Execution,
+    Error, Options, Result,
+};
+
+/// The line which marks the trash info files written by clean, only the marked
+/// entries are purged from the freedesktop trash.
+const CLEANED: &str = "X-Cleaned-By=clean";
+
+/// Where the removed directories are moved to instead of being deleted
+/// permanently, by a cheap rename on the same file system.
+#[derive(Debug, Clone)]
+pub(crate) enum Trash {
+    /// The freedesktop trash, e.g: `~/.local/share/Trash`, each entry is moved
+    /// into `files/` with its original path in `info/<name>.trashinfo`.
+    Freedesktop(PathBuf),
+    /// The quarantine directory, each run moves the entries into `<run>/files/`
+    /// by their absolute paths, and records the original paths in `<run>/manifest`.
+    Quarantine { root: PathBuf, run: String },
+}
+
+impl Trash {
+    /// The trash chosen by the options, `None` if deleting permanently.
+    pub fn new(options: &Options) -> Result<Option<Trash>> {
+        if let Some(root) = &options.quarantine {
+            let root = conf::expand(&root.to_string_lossy()).unwrap_or(root.to_owned());
+            let root = conf::normalize(root);
+            let run = timestamp(SystemTime::now()).replace(['-', ':'], "");
+            let run = format!("{run}-{}", std::process::id());
+            return Ok(Some(Trash::Quarantine { root, run }));
+        }
+        if !options.trash {
+            return Ok(None);
+        }
+        let data = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|data| data.is_absolute())
+            .or_else(|| home::home_dir().map(|home| home.join(".local/share")));
+        match data {
+            Some(data) => Ok(Some(Trash::Freedesktop(data.join("Trash")))),
+            None => Err(Error::other("trash not found, use --quarantine instead"))?,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        match self {
+            Trash::Freedesktop(root) => root,
+            Trash::Quarantine { root, .. } => root,
+        }
+    }
+
+    /// Moves the `path` into the trash, a symlink is moved itself rather than
+    /// the target.
+    pub fn dispose(&self, path: &Path) -> io::Result<()> {
+        let path = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => conf::normalize(parent).join(name),
+            _ => conf::normalize(path),
+        };
+        match self {
+            Trash::Freedesktop(root) => {
+                // a trash on another file system can't be moved into, so it's
+                // moved into the trash at the top of its own file system
+                return match trash(root, &path, &path) {
+                    Err(err) if crosses_devices(&err) => match topdir(&path) {
+                        Some(topdir) => {
+                            let root = topdir.join(format!(".Trash-{}", uid()));
+                            create_private_dir(&root)?;
+                            let original = path.strip_prefix(&topdir).unwrap_or(&path);
+                            trash(&root, &path, original)
+                        }
+                        None => Err(err),
+                    },
+                    result => result,
+                };
+            }
+            Trash::Quarantine { root, run } => {
+                let run = root.join(run);
+                let quarantined = run.join("files").join(relative(&path));
+                fs::create_dir_all(quarantined.parent().unwrap_or(&run))?;
+                rename(&path, &quarantined)?;
+                let mut manifest = fs::File::options()
+                    .create(true)
+                    .append(true)
+                    .open(run.join("manifest"))?;
+                // encoded as the trash info, a line per path of any bytes
+                return writeln!(manifest, "{}", encode(&path));
+            }
+        }
+
+        /// Moves the `path` into the freedesktop trash `root`, and records the
+        /// `original` path, which is relative to the top dir of a top dir trash.
+        fn trash(root: &Path, path: &Path, original: &Path) -> io::Result<()> {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let (files, info) = (root.join("files"), root.join("info"));
+            fs::create_dir_all(&files)?;
+            fs::create_dir_all(&info)?;
+            // the info file is created exclusively to claim a unique name
+            for n in 1.. {
+                let name = match n {
+                    1 => name.to_string(),
+                    n => format!("{name}.{n}"),
+                };
+                let info = info.join(format!("{name}.trashinfo"));
+                let mut file = match fs::File::options().write(true).create_new(true).open(&info) {
+                    Ok(file) => file,
+                    Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                    Err(err) => return Err(err),
+                };
+                write!(
+                    file,
+                    "[Trash Info]\nPath={}\nDeletionDate={}\n{CLEANED}\n",
+                    encode(original),
+                    local_timestamp(SystemTime::now())
+                )?;
+                return fs::rename(path, files.join(&name)).map_err(|err| {
+                    let _ = fs::remove_file(&info);
+                    err
+                });
+            }
+            unreachable!()
+        }
+    }
+
+    /// Removes the entries moved into the trash by clean before `before`, or
+    /// all of them if `before` is absent, the other entries are left untouched.
+    pub fn purge(&self, before: Option<SystemTime>) -> io::Result<()> {
+        let expired = |path: &Path| match (before, fs::metadata(path)) {
+            (None, _) => true,
+            (Some(before), Ok(meta)) => meta.modified().is_ok_and(|time| time < before),
+            _ => false,
+        };
+        match self {
+            Trash::Freedesktop(root) => {
+                let Ok(infos) = fs::read_dir(root.join("info")) else {
+                    return Ok(());
+                };
+                for info in infos {
+                    let info = info?.path();
+                    let Some(name) = info
+                        .file_name()
+                        .and_then(|name| name.to_str()?.strip_suffix(".trashinfo"))
+                    else {
+                        continue;
+                    };
+                    let cleaned = fs::read_to_string(&info)
+                        .is_ok_and(|info| info.lines().any(|line| line == CLEANED));
+                    if !cleaned || !expired(&info) {
+                        continue;
+                    }
+                    match root.join("files").join(name) {
+                        file if file.is_symlink() || file.is_file() => fs::remove_file(file)?,
+                        dir if dir.is_dir() => remove_dir_all::remove_dir_all(dir)?,
+                        _ => {}
+                    }
+                    fs::remove_file(info)?;
+                }
+            }
+            Trash::Quarantine { root, .. } => {
+                let Ok(runs) = fs::read_dir(root) else {
+                    return Ok(());
+                };
+                for run in runs {
+                    let run = run?.path();
+                    let named = run.file_name().and_then(|name| name.to_str());
+                    let manifest = run.join("manifest");
+                    if named.and_then(parse_run).is_some()
+                        && manifest.is_file()
+                        && expired(&manifest)
+                    {
+                        remove_dir_all::remove_dir_all(run)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Removes the entries moved into the `trash` more than `older_than` ago, or
+/// all of the entries if `older_than` is absent.
+pub(crate) fn purge(trash: Trash, older_than: Option<Duration>) -> Execution<'static> {
+    let root = trash.root().to_owned();
+    let before = older_than.map(|age| {
+        SystemTime::now()
+            .checked_sub(age)
+            .unwrap_or(SystemTime::UNIX_EPOCH)
+    });
+    let mut purge = Execution::new(Plan::Purge(trash, before), root.clone());
+    purge.measured = vec![root];
+    return purge;
+}
+
+/// Moves the entries of the latest run in the quarantine `root` back to their
+/// original paths, the recreated original paths are reported as conflicts and
+/// left in the quarantine, which makes the restore unclean.
+pub(crate) async fn restore(root: &Path) -> Result<bool> {
+    let Some(run) = latest(root) else {
+        return Err(Error::other(format!(
+            "nothing to restore in {}",
+            root.display()
+        )))?;
+    };
+    let manifest = tokio::fs::read_to_string(run.join("manifest")).await?;
+    let (mut clean, mut left) = (true, vec![]);
+    for line in manifest.lines().filter(|line| !line.is_empty()) {
+        let Some(original) = decode(line) else {
+            return Err(Error::other(format!("malformed manifest line: `{line}`")))?;
+        };
+        let quarantined = run.join("files").join(relative(&original));
+        let exe = Execution::new(Plan::Restore(quarantined.clone()), original.clone());
+        if quarantined.symlink_metadata().is_err() {
+            exe.skip("not found in quarantine")?;
+            continue;
+        }
+        if original.symlink_metadata().is_ok() {
+            exe.skip("conflict, the original path exists")?;
+            clean = false;
+            left.push(line);
+            continue;
+        }
+        if !exe.run().await.unwrap_or(false) {
+            clean = false;
+            left.push(line);
+        }
+    }
+    match left.is_empty() {
+        true => remove_dir_all::remove_dir_all(run)?,
+        false => tokio::fs::write(run.join("manifest"), left.join("\n") + "\n").await?,
+    }
+    return Ok(clean);
+
+    /// The latest run has the greatest timestamp in its name, and the latest
+    /// modified manifest among the runs started within the same second.
+    fn latest(root: &Path) -> Option<PathBuf> {
+        let runs = fs::read_dir(root).ok()?.flatten().filter_map(|run| {
+            let name = run.file_name().into_string().ok()?;
+            let (time, _) = parse_run(&name)?;
+            let modified = fs::metadata(run.path().join("manifest"))
+                .and_then(|manifest| manifest.modified())
+                .ok()?;
+            Some(((time.to_string(), modified), run.path()))
+        });
+        return runs.max_by(|(a, _), (b, _)| a.cmp(b)).map(|(_, run)| run);
+    }
+}
+
+/// The timestamp and the pid of the quarantine run `name`, which is formatted
+/// as `YYYYMMDDTHHMMSS-pid`, e.g: `20230922T083000-1234`.
+fn parse_run(name: &str) -> Option<(&str, u32)> {
+    let (time, pid) = name.split_once('-')?;
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    let (date, clock) = time.split_once('T')?;
+    if date.len() != 8 || clock.len() != 6 || !digits(date) || !digits(clock) {
+        return None;
+    }
+    return Some((time, pid.parse().ok()?));
+}
+
+/// The top directory of the file system holding the `path`, i.e. its furthest
+/// ancestor on the same device.
+#[cfg(unix)]
+fn topdir(path: &Path) -> Option<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+    let device = fs::symlink_metadata(path).ok()?.dev();
+    let same = |dir: &&Path| fs::metadata(dir).is_ok_and(|meta| meta.dev() == device);
+    return path
+        .ancestors()
+        .skip(1)
+        .take_while(same)
+        .last()
+        .map(Path::to_owned);
+}
+
+#[cfg(not(unix))]
+fn topdir(_: &Path) -> Option<PathBuf> {
+    None
+}
+
+#[cfg(unix)]
+fn crosses_devices(err: &io::Error) -> bool {
+    err.raw_os_error() == Some(libc::EXDEV)
+}
+
+#[cfg(not(unix))]
+fn crosses_devices(_: &io::Error) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn uid() -> u32 {
+    // SAFETY: getuid is always successful
+    unsafe { libc::getuid() }
+}
+
+#[cfg(not(unix))]
+fn uid() -> u32 {
+    0
+}
+
+/// Creates the `dir` accessible to the user only, if absent.
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    match builder.create(dir) {
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists && dir.is_dir() => Ok(()),
+        result => result,
+    }
+}
+
+/// The `path` without the root or prefix, to mirror it under another directory.
+pub(crate) fn relative(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+/// Moves the `path` to `to`, by copying and then removing it across the file
+/// systems, e.g: a quarantine directory on another disk.
+pub(crate) fn rename(path: &Path, to: &Path) -> io::Result<()> {
+    let remove = |path: &Path| match fs::symlink_metadata(path)?.is_dir() {
+        true => remove_dir_all::remove_dir_all(path),
+        false => fs::remove_file(path),
+    };
+    return match fs::rename(path, to) {
+        Err(err) if crosses_devices(&err) => match disk::copy(path, to) {
+            Ok(()) => remove(path),
+            Err(err) => {
+                let _ = remove(to);
+                Err(err)
+            }
+        },
+        result => result,
+    };
+}
+
+/// Percent-encodes the `path` as the freedesktop trash requires, which keeps
+/// the bytes of the path, e.g: a newline or an invalid UTF-8 sequence.
+fn encode(path: &Path) -> String {
+    #[cfg(unix)]
+    let path = std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str());
+    #[cfg(not(unix))]
+    let path = path.to_string_lossy();
+    #[cfg(not(unix))]
+    let path = path.as_bytes();
+    return path
+        .iter()
+        .map(|&byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{byte:02X}"),
+        })
+        .collect();
+}
+
+/// Decodes the `path` percent-encoded by `encode`, `None` if malformed.
+fn decode(path: &str) -> Option<PathBuf> {
+    let (mut bytes, mut rest) = (vec![], path.as_bytes());
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+        let hex = tail
+            .get(..2)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+        bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+        rest = &tail[2..];
+    }
+    #[cfg(unix)]
+    return Some(<std::ffi::OsString as std::os::unix::ffi::OsStringExt>::from_vec(bytes).into());
+    #[cfg(not(unix))]
+    return String::from_utf8(bytes).ok().map(PathBuf::from);
+}
+
+/// The `time` in the local time zone as the freedesktop trash requires, e.g:
+/// `2023-09-22T16:30:00` in UTC+8, or in UTC off unix.
+fn local_timestamp(time: SystemTime) -> String {
+    #[cfg(unix)]
+    let offset = {
+        let secs = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |secs| secs.as_secs()) as libc::time_t;
+        // SAFETY: the `tm` is plain data filled by localtime_r, which is thread-safe
+        let mut tm = unsafe { std::mem::zeroed::<libc::tm>() };
+        match unsafe { libc::localtime_r(&secs, &mut tm) }.is_null() {
+            true => 0,
+            false => tm.tm_gmtoff as i64,
+        }
+    };
+    #[cfg(not(unix))]
+    let offset = 0i64;
+    let local = match Duration::from_secs(offset.unsigned_abs()) {
+        east if offset >= 0 => time.checked_add(east),
+        west => time.checked_sub(west),
+    };
+    return timestamp(local.unwrap_or(time));
+}
+
+/// The `time` in UTC, e.g: `2023-09-22T08:30:00`.
+pub(crate) fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    // civil from days, see: http://howardhinnant.github.io/date_algorithms.html
+    let (days, secs) = ((secs / 86400) as i64 + 719468, secs % 86400);
+    let (era, doe) = (days.div_euclid(146097), days.rem_euclid(146097));
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    return format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        path::Path,
+        time::{Duration, SystemTime},
+    };
+
+    use super::{decode, encode, parse_run, restore, timestamp, Trash, CLEANED};
+    use crate::test::{temp_dir, RmDirGuard};
+
+    #[test]
+    fn utc_timestamp() {
+        let time = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        assert_eq!(timestamp(time(0)), "1970-01-01T00:00:00");
+        assert_eq!(timestamp(time(951782400)), "2000-02-29T00:00:00");
+        assert_eq!(timestamp(time(1695371400)), "2023-09-22T08:30:00");
+    }
+
+    #[test]
+    fn percent_encoded_path() {
+        assert_eq!(encode(Path::new("/a b/c%d")), "/a%20b/c%25d");
+        assert_eq!(encode(Path::new("/a\nb")), "/a%0Ab");
+        assert_eq!(decode("/a%20b/c%25d").unwrap(), Path::new("/a b/c%d"));
+        assert_eq!(decode("/a%0Ab").unwrap(), Path::new("/a\nb"));
+        assert_eq!(decode("/a%2"), None);
+        assert_eq!(decode("/a%+1"), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn percent_encoded_path_of_any_bytes() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+        let path = Path::new(OsStr::from_bytes(b"/a\xffb"));
+        assert_eq!(encode(path), "/a%FFb");
+        assert_eq!(decode(&encode(path)).unwrap(), path);
+    }
+
+    #[test]
+    fn dispose_into_freedesktop_trash() {
+        let test = temp_dir("clean-trash");
+        let _guard = RmDirGuard(&test);
+        fs::create_dir_all(test.join("a/target")).unwrap();
+        fs::create_dir_all(test.join("b/target")).unwrap();
+        fs::create_dir_all(test.join("Trash/files/other")).unwrap();
+        fs::create_dir_all(test.join("Trash/info")).unwrap();
+        let other = "[Trash Info]\nPath=/other\nDeletionDate=2023-09-22T08:30:00\n";
+        fs::write(test.join("Trash/info/other.trashinfo"), other).unwrap();
+        let trash = Trash::Freedesktop(test.join("Trash"));
+
+        trash.dispose(&test.join("a/target")).unwrap();
+        trash.dispose(&test.join("b/target")).unwrap();
+        assert!(!test.join("a/target").exists());
+        assert!(test.join("Trash/files/target").is_dir());
+        assert!(test.join("Trash/files/target.2").is_dir());
+        let info = fs::read_to_string(test.join("Trash/info/target.2.trashinfo")).unwrap();
+        assert!(info.starts_with("[Trash Info]\nPath=/"));
+        assert!(info.contains("/b/target\nDeletionDate="));
+        assert!(info.ends_with(&format!("{CLEANED}\n")));
+
+        trash
+            .purge(Some(SystemTime::now() - Duration::from_secs(60)))
+            .unwrap();
+        assert!(test.join("Trash/files/target").exists());
+        trash.purge(None).unwrap();
+        assert!(!test.join("Trash/files/target").exists());
+        assert!(!test.join("Trash/info/target.trashinfo").exists());
+        assert!(
+            test.join("Trash/files/other").is_dir(),
+            "not trashed by clean"
+        );
+        assert!(test.join("Trash/info/other.trashinfo").is_file());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn topdir_on_the_same_device() {
+        use std::os::unix::fs::MetadataExt;
+        let test = temp_dir("clean-topdir");
+        let _guard = RmDirGuard(&test);
+        fs::create_dir_all(test.join("a/target")).unwrap();
+        let device = |path: &Path| fs::metadata(path).unwrap().dev();
+
+        let topdir = super::topdir(&test.join("a/target")).unwrap();
+        assert!(test.starts_with(&topdir));
+        assert_eq!(device(&topdir), device(&test));
+        if let Some(parent) = topdir.parent() {
+            assert_ne!(device(parent), device(&topdir));
+        }
+    }
+
+    #[test]
+    fn parse_run_names() {
+        assert_eq!(
+            parse_run("20230922T083000-1234"),
+            Some(("20230922T083000", 1234))
+        );
+        assert_eq!(parse_run("20230922T0830-1234"), None);
+        assert_eq!(parse_run("20230922T083000-pid"), None);
+        assert_eq!(parse_run("photos"), None);
+    }
+
+    #[tokio::test]
+    async fn restore_latest_run() {
+        let test = temp_dir("clean-restore");
+        let _guard = RmDirGuard(&test);
+        for dir in ["a/target", "b/target", "c/target"] {
+            fs::create_dir_all(test.join(dir)).unwrap();
+        }
+        let root = test.join("quarantine");
+        let quarantine = |run: &str| Trash::Quarantine {
+            root: root.clone(),
+            run: run.to_string(),
+        };
+        // the pid of the earlier run is greater as text
+        let (earlier, latest) = ("20230922T083000-9", "20230922T083001-10");
+        quarantine(earlier).dispose(&test.join("a/target")).unwrap();
+        quarantine(latest).dispose(&test.join("b/target")).unwrap();
+        quarantine(latest).dispose(&test.join("c/target")).unwrap();
+        fs::create_dir_all(test.join("c/target")).unwrap();
+
+        assert!(!restore(&root).await.unwrap(), "conflict");
+        assert!(test.join("b/target").is_dir());
+        assert!(!test.join("a/target").exists(), "only the latest run");
+        let manifest = fs::read_to_string(root.join(latest).join("manifest")).unwrap();
+        assert!(manifest.trim().ends_with("c/target"), "conflict is left");
+
+        fs::remove_dir(test.join("c/target")).unwrap();
+        assert!(restore(&root).await.unwrap());
+        assert!(test.join("c/target").is_dir());
+        assert!(!root.join(latest).exists());
+    }
+
+    #[test]
+    fn dispose_into_quarantine() {
+        let test = temp_dir("clean-quarantine");
+        let _guard = RmDirGuard(&test);
+        fs::create_dir_all(test.join("a/target")).unwrap();
+        fs::create_dir_all(test.join("quarantine/photos")).unwrap();
+        fs::write(test.join("quarantine/photos/manifest"), "").unwrap();
+        fs::create_dir_all(test.join("quarantine/20230922T083000-1")).unwrap();
+        let root = test.join("quarantine");
+        let run = "20230922T083000-2".to_string();
+        let quarantine = Trash::Quarantine { root, run };
+
+        quarantine.dispose(&test.join("a/target")).unwrap();
+        assert!(!test.join("a/target").exists());
+        let run = test.join("quarantine/20230922T083000-2");
+        let manifest = fs::read_to_string(run.join("manifest")).unwrap();
+        let original = Path::new(manifest.trim());
+        assert!(original.ends_with("a/target"));
+        let quarantined = run.join("files").join(super::relative(original));
+        assert!(quarantined.is_dir());
+
+        quarantine
+            .purge(Some(SystemTime::now() - Duration::from_secs(60)))
+            .unwrap();
+        assert!(quarantined.is_dir());
+        quarantine.purge(None).unwrap();
+        assert!(!run.exists());
+        assert!(test.join("quarantine/photos").is_dir(), "not a run");
+        assert!(
+            test.join("quarantine/20230922T083000-1").is_dir(),
+            "no manifest"
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn quarantine_paths_of_newlines() {
+        let test = temp_dir("clean-newline");
+        let _guard = RmDirGuard(&test);
+        let original = test.join("a\nb/target");
+        fs::create_dir_all(&original).unwrap();
+        let root = test.join("quarantine");
+        let run = "20230922T083000-1".to_string();
+        let quarantine = Trash::Quarantine {
+            root: root.clone(),
+            run,
+        };
+
+        quarantine.dispose(&original).unwrap();
+        assert!(!original.exists());
+        let manifest = fs::read_to_string(root.join("20230922T083000-1/manifest")).unwrap();
+        assert_eq!(manifest.lines().count(), 1);
+        assert!(restore(&root).await.unwrap());
+        assert!(original.is_dir());
+    }
+
+    #[tokio::test]
+    #[cfg(target_os = "linux")]
+    async fn quarantine_across_file_systems() {
+        use std::os::unix::fs::{symlink, MetadataExt};
+        let test = temp_dir("clean-cross");
+        let other = Path::new("/dev/shm").join(test.file_name().unwrap());
+        let device = |path: &Path| fs::metadata(path).map(|meta| meta.dev()).ok();
+        if device(Path::new("/dev/shm")).is_none()
+            || device(Path::new("/dev/shm")) == device(&std::env::temp_dir())
+        {
+            return;
+        }
+        let (_guard, _other) = (RmDirGuard(&test), RmDirGuard(&other));
+        fs::create_dir_all(test.join("a/target/debug")).unwrap();
+        fs::write(test.join("a/target/debug/a"), "a").unwrap();
+        symlink("debug/a", test.join("a/target/link")).unwrap();
+        let run = "20230922T083000-1".to_string();
+        let quarantine = Trash::Quarantine {
+            root: other.clone(),
+            run,
+        };
+
+        quarantine.dispose(&test.join("a/target")).unwrap();
+        assert!(!test.join("a/target").exists());
+        assert!(restore(&other).await.unwrap());
+        let a = fs::read_to_string(test.join("a/target/debug/a")).unwrap();
+        assert_eq!(a, "a");
+        let link = fs::read_link(test.join("a/target/link")).unwrap();
+        assert_eq!(link, Path::new("debug/a"));
+    }
+}
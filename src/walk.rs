@@ -0,0 +1,550 @@
+use std::{
+    collections::HashSet,
+    fs::Metadata,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use async_recursion::async_recursion;
+use glob::{MatchOptions, Pattern};
+use tokio::fs;
+
+use crate::{
+    conf::{self, Config, Plan},
+    exec::Execution,
+    Error, IOResult, Result,
+};
+
+/// Walks the directories to collect the executions by the traversal policy:
+/// symlinks are not followed unless enabled, directories are visited once to
+/// break the cycles, and optionally stay on the file system of the start directory.
+pub(crate) struct Walker<'a> {
+    config: &'a Config,
+    /// The device of the start directory if staying on one file system.
+    device: Option<u64>,
+    visited: HashSet<FileId>,
+    filter: Filter,
+    /// The directories removed by the plans of the ancestors being walked.
+    removing: Vec<PathBuf>,
+    executions: Vec<Execution<'static>>,
+}
+
+impl<'a> Walker<'a> {
+    pub async fn walk(entry: &Path, config: &'a Config) -> Result<Vec<Execution<'static>>> {
+        let meta = fs::metadata(entry).await?;
+        let device = match device(&meta) {
+            _ if !config.options.one_file_system => None,
+            Some(device) => Some(device),
+            None => Err(Error::other("--one-file-system is supported on unix only"))?,
+        };
+        let mut walker = Walker {
+            config,
+            device,
+            visited: HashSet::from([id(entry, &meta)]),
+            filter: Filter::new(entry, &config.options.include, &config.options.exclude)?,
+            removing: vec![],
+            executions: vec![],
+        };
+        walker.collect(entry, 0, false).await?;
+        return Ok(walker.executions);
+    }
+
+    /// Collects the executions under the `entry` at the `depth` recursively,
+    /// returns the latest modified time of the files under the `entry`.
+    ///
+    /// The files matching the globs are removed only inside the `project`, i.e.
+    /// the entry or any of its ancestors is marked as a project, unless the
+    /// conditions of the glob rule hold.
+    ///
+    /// Directories beyond the max depth are still walked for the modified time
+    /// if the projects are filtered by age, but nothing is collected from them,
+    /// as well as the directories removed by the plans of the walk, e.g: the
+    /// `build/Makefile` generated by CMake is never cleaned by `make clean`.
+    #[async_recursion(?Send)]
+    async fn collect(&mut self, entry: &Path, depth: usize, project: bool) -> IOResult<SystemTime> {
+        macro_rules! try_unwrap {
+            ($exp: expr) => {
+                match $exp {
+                    Ok(value) => value,
+                    Err(err) => match err.kind() {
+                        std::io::ErrorKind::NotFound => return Ok(SystemTime::UNIX_EPOCH),
+                        _ => return Err(err),
+                    },
+                }
+            };
+        }
+        let mut dir = try_unwrap!(fs::read_dir(entry).await);
+        let mut modified = try_unwrap!(fs::symlink_metadata(entry).await).modified()?;
+        let mut plans = vec![];
+        let options = &self.config.options;
+        let collects = depth >= options.min_depth
+            && options.max_depth.map_or(true, |max| depth <= max)
+            && !self.removing.iter().any(|dir| entry.starts_with(dir));
+        let descends =
+            options.max_depth.map_or(true, |max| depth < max) || options.older_than.is_some();
+
+        let mut entries = vec![];
+        while let Some(current) = try_unwrap!(dir.next_entry().await) {
+            if let Ok(time) = current.metadata().await.and_then(|meta| meta.modified()) {
+                modified = modified.max(time);
+            }
+            entries.push(current.path());
+        }
+        let project = project || entries.iter().any(|path| self.config.marks(path));
+
+        for current in &entries {
+            // the paths are filtered before the rules are looked up
+            if !collects || !self.filter.accepts(current) {
+                continue;
+            }
+            let plan = self.config.parse(current).filter(|plan| {
+                project || !matches!(plan, Plan::RmFiles(_)) || self.config.conditional(plan)
+            });
+            if let Some(plan) = plan {
+                // files matching the same glob are removed by one plan per
+                // directory, as well as the same command runs once, e.g:
+                // `dotnet clean` for both `App.sln` and `App.csproj`.
+                let duplicated = plans.iter().any(|each| match (each, &plan) {
+                    (Plan::RmFiles(a), Plan::RmFiles(b)) => a == b,
+                    (Plan::Cmd(a), Plan::Cmd(b)) => a.command == b.command && a.args == b.args,
+                    _ => false,
+                });
+                if !duplicated {
+                    plans.push(plan);
+                }
+            }
+        }
+
+        // all plans of the entry are parsed before descending, since a rule
+        // may remove a directory listed before its file, e.g: `.gradle/`
+        let removing = self.removing.len();
+        let removed = plans.iter().flat_map(|plan| plan.removes());
+        self.removing.extend(removed.map(|dir| entry.join(dir)));
+        for current in entries {
+            if descends && self.enters(&current).await {
+                modified = modified.max(self.collect(&current, depth + 1, project).await?);
+            }
+        }
+        self.removing.truncate(removing);
+
+        self.executions
+            .extend(plans.into_iter().map(|plan| Execution {
+                modified,
+                ..Execution::new(plan, entry.to_owned())
+            }));
+        return Ok(modified);
+    }
+
+    /// Whether to descend into the `path` by the traversal policy.
+    async fn enters(&mut self, path: &Path) -> bool {
+        let Ok(mut meta) = fs::symlink_metadata(path).await else {
+            return false;
+        };
+        if meta.is_symlink() {
+            if !self.config.options.follow_symlinks {
+                return false;
+            }
+            match fs::metadata(path).await {
+                Ok(target) => meta = target,
+                Err(_) => return false,
+            }
+        }
+        if !meta.is_dir() || (self.device.is_some() && device(&meta) != self.device) {
+            return false;
+        }
+        if self.filter.excludes(path) {
+            return false;
+        }
+        return self.visited.insert(id(path, &meta));
+    }
+}
+
+/// Include and exclude globs of the candidate paths. A path matches a glob if
+/// the path or any of its ancestors matches. Globs without a separator match
+/// any file name below the start directory, e.g: `release-*`, the others match
+/// the absolute paths, relative globs are resolved against the start directory.
+struct Filter {
+    root: PathBuf,
+    absolute: PathBuf,
+    include: Vec<(Pattern, bool)>,
+    exclude: Vec<(Pattern, bool)>,
+}
+
+impl Filter {
+    fn new(root: &Path, include: &[String], exclude: &[String]) -> Result<Filter> {
+        let absolute = conf::normalize(root);
+        let escaped = Pattern::escape(&absolute.to_string_lossy());
+        let compile = |globs: &[String]| -> Result<Vec<(Pattern, bool)>> {
+            let compile = |glob: &String| {
+                let by_name = !glob.contains(['/', '\\']);
+                let glob = match by_name {
+                    true => glob.to_owned(),
+                    _ => match conf::expand(glob) {
+                        // the start directory is matched literally, e.g: `[x]`
+                        Some(path) => Path::new(&escaped)
+                            .join(path)
+                            .to_string_lossy()
+                            .into_owned(),
+                        None => glob.to_owned(),
+                    },
+                };
+                match Pattern::new(&glob) {
+                    Ok(pattern) => Ok((pattern, by_name)),
+                    Err(err) => Err(Error::other(format!("invalid glob `{glob}`: {err}")))?,
+                }
+            };
+            globs.iter().map(compile).collect()
+        };
+        return Ok(Filter {
+            root: root.to_owned(),
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+            absolute,
+        });
+    }
+
+    fn accepts(&self, path: &Path) -> bool {
+        (self.include.is_empty() || self.matches(&self.include, path)) && !self.excludes(path)
+    }
+
+    fn excludes(&self, path: &Path) -> bool {
+        self.matches(&self.exclude, path)
+    }
+
+    fn matches(&self, patterns: &[(Pattern, bool)], path: &Path) -> bool {
+        const OPTIONS: MatchOptions = MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        let absolute = self.absolute.join(relative);
+        return patterns.iter().any(|(pattern, by_name)| match by_name {
+            true => relative
+                .components()
+                .any(|name| (name.as_os_str().to_str()).is_some_and(|name| pattern.matches(name))),
+            _ => absolute
+                .ancestors()
+                .any(|path| pattern.matches_path_with(path, OPTIONS)),
+        });
+    }
+}
+
+#[cfg(unix)]
+type FileId = (u64, u64);
+
+#[cfg(unix)]
+fn id(_: &Path, meta: &Metadata) -> FileId {
+    use std::os::unix::fs::MetadataExt;
+    (meta.dev(), meta.ino())
+}
+
+#[cfg(unix)]
+fn device(meta: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(meta.dev())
+}
+
+#[cfg(not(unix))]
+type FileId = std::path::PathBuf;
+
+#[cfg(not(unix))]
+fn id(path: &Path, _: &Metadata) -> FileId {
+    crate::conf::normalize(path)
+}
+
+/// Devices are not available, so staying on one file system is rejected.
+#[cfg(not(unix))]
+fn device(_: &Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, time::SystemTime};
+
+    use super::Walker;
+    use crate::{
+        conf::{Config, Plan},
+        test::RmDirGuard,
+    };
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "test-walk-{}",
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ))
+    }
+
+    #[tokio::test]
+    async fn collect_within_depth() {
+        let test = temp_dir();
+        let _guard = RmDirGuard(&test);
+        for dir in ["", "a", "a/b"] {
+            fs::create_dir_all(test.join(dir)).unwrap();
+            fs::write(test.join(dir).join("Cargo.toml"), "").unwrap();
+        }
+        let depths = |min_depth, max_depth| {
+            let mut config = Config::empty();
+            config.options.min_depth = min_depth;
+            config.options.max_depth = max_depth;
+            let test = &test;
+            async move {
+                let executions = Walker::walk(test, &config).await.unwrap();
+                let mut depths = executions
+                    .iter()
+                    .map(|exe| {
+                        exe.work_dir
+                            .strip_prefix(test)
+                            .unwrap()
+                            .components()
+                            .count()
+                    })
+                    .collect::<Vec<_>>();
+                depths.sort();
+                depths
+            }
+        };
+
+        assert_eq!(depths(0, None).await, [0, 1, 2]);
+        assert_eq!(depths(1, None).await, [1, 2]);
+        assert_eq!(depths(0, Some(1)).await, [0, 1]);
+        assert_eq!(depths(1, Some(1)).await, [1]);
+    }
+
+    #[tokio::test]
+    async fn collect_files_once_per_directory() {
+        let test = temp_dir();
+        let _guard = RmDirGuard(&test);
+        for file in ["pom.xml", "a.log", "b.log", "c.txt", "sub/d.log"] {
+            fs::create_dir_all(test.join(file).parent().unwrap()).unwrap();
+            fs::write(test.join(file), "").unwrap();
+        }
+        let config = Config::load(b"*.log".as_ref()).await.unwrap();
+
+        let executions = Walker::walk(&test, &config).await.unwrap();
+        let mut dirs = executions
+            .iter()
+            .filter(|exe| matches!(exe.plan, Plan::RmFiles(_)))
+            .map(|exe| exe.work_dir.clone())
+            .collect::<Vec<_>>();
+        dirs.sort();
+        assert_eq!(dirs, [test.clone(), test.join("sub")]);
+    }
+
+    #[tokio::test]
+    async fn collect_files_inside_projects_only() {
+        let test = temp_dir();
+        let _guard = RmDirGuard(&test);
+        let files = [
+            "a.log",
+            "project/Cargo.toml",
+            "project/b.log",
+            "project/logs/c.log",
+            "misc/d.log",
+            "misc/keep",
+            "misc/e.tmp",
+            "f.tmp",
+        ];
+        for file in files {
+            fs::create_dir_all(test.join(file).parent().unwrap()).unwrap();
+            fs::write(test.join(file), "").unwrap();
+        }
+        let config = Config::load(b"*.log\n*.tmp if keep".as_ref())
+            .await
+            .unwrap();
+
+        let executions = Walker::walk(&test, &config).await.unwrap();
+        let mut dirs = executions
+            .iter()
+            .filter(|exe| matches!(exe.plan, Plan::RmFiles(_)))
+            .map(|exe| exe.work_dir.clone())
+            .collect::<Vec<_>>();
+        dirs.sort();
+        let expected = [
+            test.join("misc"),
+            test.join("project"),
+            test.join("project/logs"),
+        ];
+        assert_eq!(dirs, expected);
+    }
+
+    #[tokio::test]
+    async fn collect_same_command_once_per_directory() {
+        let test = temp_dir();
+        let _guard = RmDirGuard(&test);
+        fs::create_dir_all(&test).unwrap();
+        for file in ["App.sln", "App.csproj", "Cargo.toml"] {
+            fs::write(test.join(file), "").unwrap();
+        }
+
+        let executions = Walker::walk(&test, &Config::empty()).await.unwrap();
+        let mut cmds = executions
+            .iter()
+            .map(|exe| exe.plan.cmd().to_string())
+            .collect::<Vec<_>>();
+        cmds.sort();
+        assert_eq!(cmds, ["cargo", "dotnet"]);
+    }
+
+    #[tokio::test]
+    async fn collect_nothing_under_removed_dirs() {
+        let test = temp_dir();
+        let _guard = RmDirGuard(&test);
+        let files = [
+            "CMakeLists.txt",
+            "build/Makefile",
+            "web/package.json",
+            "web/node_modules/dep/Makefile",
+        ];
+        for file in files {
+            fs::create_dir_all(test.join(file).parent().unwrap()).unwrap();
+            fs::write(test.join(file), "clean:\n\trm -rf out\n").unwrap();
+        }
+        let config = Config::load(b"node_modules/".as_ref()).await.unwrap();
+
+        let executions = Walker::walk(&test, &config).await.unwrap();
+        let mut plans = executions
+            .iter()
+            .map(|exe| (exe.plan.cmd().to_string(), exe.work_dir.clone()))
+            .collect::<Vec<_>>();
+        plans.sort();
+        let expected = [
+            ("rm".to_string(), test.clone()),
+            ("rm".to_string(), test.join("web")),
+        ];
+        assert_eq!(plans, expected);
+    }
+
+    #[tokio::test]
+    async fn collect_included_but_not_excluded() {
+        let test = temp_dir();
+        let _guard = RmDirGuard(&test);
+        for dir in [
+            "a/services/api",
+            "a/services/web",
+            "b/services/api",
+            "release-1/x",
+        ] {
+            fs::create_dir_all(test.join(dir)).unwrap();
+            fs::write(test.join(dir).join("Cargo.toml"), "").unwrap();
+        }
+        let collect = |include: &[&str], exclude: &[&str]| {
+            let mut config = Config::empty();
+            config.options.include = include.iter().map(|s| s.to_string()).collect();
+            config.options.exclude = exclude.iter().map(|s| s.to_string()).collect();
+            let test = &test;
+            async move {
+                let executions = Walker::walk(test, &config).await.unwrap();
+                let mut dirs = executions
+                    .iter()
+                    .map(|exe| exe.work_dir.strip_prefix(test).unwrap().to_owned())
+                    .map(|dir| dir.to_string_lossy().replace('\\', "/"))
+                    .collect::<Vec<_>>();
+                dirs.sort();
+                dirs
+            }
+        };
+
+        let all = [
+            "a/services/api",
+            "a/services/web",
+            "b/services/api",
+            "release-1/x",
+        ];
+        assert_eq!(collect(&[], &[]).await, all);
+        assert_eq!(collect(&[], &["release-*"]).await, all[..3]);
+        assert_eq!(
+            collect(&["**/services/*"], &["a/*/web"]).await,
+            ["a/services/api", "b/services/api"]
+        );
+        let absolute = format!("{}/a/**", crate::conf::normalize(&test).display());
+        assert_eq!(collect(&[&absolute], &[]).await, all[..2]);
+    }
+
+    #[tokio::test]
+    async fn collect_included_under_root_of_glob_chars() {
+        let test = temp_dir();
+        let _guard = RmDirGuard(&test);
+        let root = test.join("[ab]?*");
+        for dir in ["a/x", "b/y"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+            fs::write(root.join(dir).join("Cargo.toml"), "").unwrap();
+        }
+        let mut config = Config::empty();
+        config.options.include = vec!["a/*".to_string()];
+
+        let executions = Walker::walk(&root, &config).await.unwrap();
+        assert_eq!(executions.len(), 1);
+        assert_eq!(executions[0].work_dir, root.join("a/x"));
+    }
+
+    #[tokio::test]
+    async fn fails_with_invalid_globs() {
+        let mut config = Config::empty();
+        config.options.include = vec!["[".to_string()];
+        assert!(Walker::walk(&std::env::temp_dir(), &config).await.is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn symlinks_are_followed_once_if_enabled() {
+        use std::os::unix::fs::symlink;
+
+        let test = temp_dir();
+        let _guard = RmDirGuard(&test);
+        fs::create_dir_all(test.join("root/a")).unwrap();
+        fs::create_dir_all(test.join("elsewhere/b")).unwrap();
+        fs::write(test.join("elsewhere/b/Cargo.toml"), "").unwrap();
+        symlink(test.join("root"), test.join("root/a/loop")).unwrap();
+        symlink(test.join("elsewhere"), test.join("root/elsewhere")).unwrap();
+        symlink(test.join("elsewhere"), test.join("root/again")).unwrap();
+
+        let mut config = Config::empty();
+        let executions = Walker::walk(&test.join("root"), &config).await.unwrap();
+        assert!(
+            executions.is_empty(),
+            "symlinks are not followed by default"
+        );
+
+        config.options.follow_symlinks = true;
+        let executions = Walker::walk(&test.join("root"), &config).await.unwrap();
+        assert_eq!(executions.len(), 1, "directories are visited once");
+        assert_eq!(executions[0].plan.cmd(), "cargo");
+    }
+
+    #[tokio::test]
+    #[cfg(target_os = "linux")]
+    async fn stay_on_one_file_system_if_enabled() {
+        use std::os::unix::fs::{symlink, MetadataExt};
+
+        let (test, other) = (
+            temp_dir(),
+            PathBuf::from("/dev/shm").join(temp_dir().file_name().unwrap()),
+        );
+        let device = |path: &std::path::Path| fs::metadata(path).map(|meta| meta.dev());
+        if !other.parent().unwrap().is_dir()
+            || device(&std::env::temp_dir()).ok() == device(other.parent().unwrap()).ok()
+        {
+            return;
+        }
+        let (_guard, _other) = (RmDirGuard(&test), RmDirGuard(&other));
+        fs::create_dir_all(test.join("a")).unwrap();
+        fs::create_dir_all(other.join("b")).unwrap();
+        fs::write(test.join("a/Cargo.toml"), "").unwrap();
+        fs::write(other.join("b/Cargo.toml"), "").unwrap();
+        symlink(&other, test.join("other")).unwrap();
+
+        let mut config = Config::empty();
+        config.options.follow_symlinks = true;
+        assert_eq!(Walker::walk(&test, &config).await.unwrap().len(), 2);
+
+        config.options.one_file_system = true;
+        let executions = Walker::walk(&test, &config).await.unwrap();
+        assert_eq!(executions.len(), 1);
+        assert_eq!(executions[0].work_dir, test.join("a"));
+    }
+}
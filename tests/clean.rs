@@ -1,12 +1,12 @@
-use std::{io, path::Path};
+use std::{io, path::Path, time::Duration};
 
-use clean_rs::{clean_with_config, conf::Config, Result};
+use clean_rs::{clean_with_config, conf::Config, purge, Bytes, Result};
 use tokio::fs;
 
 #[path = "../src/test.rs"]
 mod test;
 
-use test::RmDirGuard;
+use test::{temp_dir, RmDirGuard};
 
 #[tokio::test]
 async fn clean_dir() {
@@ -62,6 +62,114 @@ async fn clean_all_generated_dirs() {
     assert!(!b.exists());
 }
 
+#[tokio::test]
+async fn clean_the_rest_when_a_tool_is_not_found() {
+    let start = temp_dir("missing-tool");
+    let _guard = RmDirGuard(&start);
+    // more projects of the missing tool than the workers
+    for i in 0..num_cpus::get() {
+        fs::create_dir_all(start.join(format!("{i}")))
+            .await
+            .unwrap();
+        fs::write(start.join(format!("{i}/tool.txt")), "")
+            .await
+            .unwrap();
+    }
+    let node_modules = start.join("web/node_modules");
+    fs::create_dir_all(&node_modules).await.unwrap();
+
+    let config = Config::load(b"tool.txt = clean-rs-missing-tool\nnode_modules/".as_ref())
+        .await
+        .unwrap();
+    assert!(clean_with_config(&start, config).await.unwrap());
+    assert!(!node_modules.exists());
+}
+
+#[tokio::test]
+async fn skip_recently_modified_projects() {
+    let start = std::env::temp_dir().join("recent");
+    let _guard = RmDirGuard(&start);
+    let _ = fs::remove_dir_all(&start).await;
+    fs::create_dir_all(&start).await.unwrap();
+    copy("tests/data", &start).await.unwrap();
+
+    let to_removed = start.join("data/target");
+    let mut config = Config::empty();
+    config.options.older_than = Some(Duration::from_secs(24 * 60 * 60));
+
+    assert!(clean_with_config(&start, config).await.unwrap());
+    assert!(
+        to_removed.exists(),
+        "recently modified project should be skipped"
+    );
+}
+
+#[tokio::test]
+async fn stop_cleaning_when_free_space_is_reached() {
+    let start = std::env::temp_dir().join("free");
+    let _guard = RmDirGuard(&start);
+    let _ = fs::remove_dir_all(&start).await;
+    fs::create_dir_all(&start).await.unwrap();
+    copy("tests/data", &start).await.unwrap();
+
+    let to_removed = start.join("data/target");
+    let mut config = Config::empty();
+    config.options.free = Some(Bytes(1));
+
+    assert!(clean_with_config(&start, config).await.unwrap());
+    assert!(to_removed.exists(), "free space is reached already");
+}
+
+#[tokio::test]
+async fn refuse_to_clean_protected_entry_path() {
+    let start = std::env::temp_dir().join("protected");
+    let _guard = RmDirGuard(&start);
+    let _ = fs::remove_dir_all(&start).await;
+    fs::create_dir_all(&start).await.unwrap();
+    copy("tests/data", &start).await.unwrap();
+
+    let protected = format!("!{}", start.display());
+    let config = Config::load(protected.as_bytes()).await.unwrap();
+    let err = clean_with_config(&start, config).await.unwrap_err();
+    assert!(err
+        .to_string()
+        .ends_with("is protected, use --force to clean it anyway"));
+    assert!(start.join("data/target").exists());
+
+    let mut config = Config::load(protected.as_bytes()).await.unwrap();
+    config.options.force = true;
+    assert!(clean_with_config(&start, config).await.unwrap());
+    assert!(
+        start.join("data/target").exists(),
+        "protected path is never cleaned"
+    );
+}
+
+#[tokio::test]
+async fn move_into_quarantine_and_purge() {
+    let start = temp_dir("quarantined");
+    let _guard = RmDirGuard(&start);
+    fs::create_dir_all(start.join("project")).await.unwrap();
+    copy("tests/data", start.join("project")).await.unwrap();
+
+    let quarantine = start.join("quarantine");
+    let mut config = Config::load(b"node_modules/".as_ref()).await.unwrap();
+    config.options.quarantine = Some(quarantine.clone());
+    assert!(clean_with_config(&start, config).await.unwrap());
+    assert!(!start.join("project/data/node_modules").exists());
+
+    let mut runs = std::fs::read_dir(&quarantine).unwrap();
+    let run = runs.next().unwrap().unwrap().path();
+    let manifest = std::fs::read_to_string(run.join("manifest")).unwrap();
+    let original = start.join("project/data/node_modules");
+    assert_eq!(manifest.trim(), original.display().to_string());
+
+    let mut config = Config::empty();
+    config.options.quarantine = Some(quarantine.clone());
+    assert!(purge(config).await.unwrap());
+    assert!(!run.exists());
+}
+
 #[tokio::test]
 async fn reports_error_if_entry_path_does_not_exists() {
     let err = clean("absent").await.unwrap_err();
@@ -76,6 +184,8 @@ async fn reports_error_if_entry_path_is_not_directory() {
     assert_eq!(err.to_string(), "Cargo.toml is not a directory");
 }
 
+// the macro adds the bounds to a where clause as well
+#[allow(clippy::multiple_bound_locations)]
 #[async_recursion::async_recursion(?Send)]
 async fn copy<S: AsRef<Path>, D: AsRef<Path>>(src: S, dest: D) -> io::Result<()> {
     let (src, dest) = (src.as_ref(), dest.as_ref());